@@ -28,6 +28,18 @@ $cur/ci-test-assertions.sh
 MMTK_VO_BIT=1 $cur/ci-build.sh
 $cur/ci-test-vo-bit.sh
 
+# Build with object pinning
+MMTK_OBJECT_PINNING=1 $cur/ci-build.sh
+$cur/ci-test-object-pinning.sh
+
+# Build with conservative stack scanning
+MMTK_VO_BIT=1 MMTK_CONSERVATIVE_STACK_SCANNING=1 $cur/ci-build.sh
+$cur/ci-test-conservative-stack-scanning.sh
+
+# Build with FinalReference processing in the binding
+MMTK_FINAL_REFERENCES=1 $cur/ci-build.sh
+$cur/ci-test-final-references.sh
+
 # Build with malloc mark sweep
 MMTK_EXTREME_ASSERTIONS=1 MMTK_MALLOC_MARK_SWEEP=1 $cur/ci-build.sh
 $cur/ci-test-malloc-mark-sweep.sh
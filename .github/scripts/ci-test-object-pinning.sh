@@ -0,0 +1,42 @@
+set -xe
+
+. $(dirname "$0")/common.sh
+
+unset JAVA_TOOL_OPTIONS
+
+run_subset() {
+    heap_multiplier=$1
+
+    runbms_dacapo2006_with_heap_multiplier antlr $heap_multiplier
+    runbms_dacapo2006_with_heap_multiplier fop $heap_multiplier
+    runbms_dacapo2006_with_heap_multiplier luindex $heap_multiplier
+}
+
+# Plans that support pinning use it for JNI critical regions.
+
+# --- Immix ---
+export MMTK_PLAN=Immix
+
+run_subset 4
+
+# --- StickyImmix ---
+export MMTK_PLAN=StickyImmix
+
+run_subset 4
+
+# --- MarkSweep ---
+export MMTK_PLAN=MarkSweep
+
+run_subset 8
+
+# Other plans fall back to the GC locker.
+
+# --- GenImmix ---
+export MMTK_PLAN=GenImmix
+
+run_subset 4
+
+# --- SemiSpace ---
+export MMTK_PLAN=SemiSpace
+
+run_subset 4
@@ -278,6 +278,21 @@ impl InstanceRefKlass {
         }
         *DISCOVERED_OFFSET
     }
+    fn soft_ref_timestamp_offset() -> i32 {
+        lazy_static! {
+            pub static ref TIMESTAMP_OFFSET: i32 =
+                unsafe { ((*UPCALLS).soft_ref_timestamp_offset)() };
+        }
+        *TIMESTAMP_OFFSET
+    }
+    /// The `timestamp` field of a `java.lang.ref.SoftReference`.  It is updated to the clock
+    /// whenever `SoftReference.get()` is called.
+    pub fn soft_ref_timestamp(oop: Oop) -> i64 {
+        unsafe {
+            oop.get_field_address(Self::soft_ref_timestamp_offset())
+                .load::<i64>()
+        }
+    }
     pub fn referent_address<const COMPRESSED: bool>(oop: Oop) -> OpenJDKSlot<COMPRESSED> {
         oop.get_field_address(Self::referent_offset()).into()
     }
@@ -310,6 +325,13 @@ pub fn set_compressed_klass_base_and_shift(base: Address, shift: usize) {
     COMPRESSED_KLASS_SHIFT.store(shift, Ordering::Relaxed);
 }
 
+pub fn compressed_klass_base_and_shift() -> (Address, usize) {
+    (
+        COMPRESSED_KLASS_BASE.load(Ordering::Relaxed),
+        COMPRESSED_KLASS_SHIFT.load(Ordering::Relaxed),
+    )
+}
+
 impl OopDesc {
     pub fn start(&self) -> Address {
         unsafe { mem::transmute(self) }
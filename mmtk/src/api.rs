@@ -76,6 +76,7 @@ pub unsafe extern "C" fn release_buffer(ptr: *mut Address, length: usize, capaci
 #[no_mangle]
 pub extern "C" fn openjdk_gc_init(calls: *const OpenJDK_Upcalls) {
     unsafe { UPCALLS = calls };
+    crate::logging::init();
     crate::abi::validate_memory_layouts();
 
     // We don't really need this, as we can dynamically set plans. However, for compatability of our CI scripts,
@@ -117,6 +118,10 @@ pub extern "C" fn openjdk_gc_init(calls: *const OpenJDK_Upcalls) {
     } else {
         lazy_static::initialize(&crate::SINGLETON_UNCOMPRESSED);
     }
+    crate::gc_stats::init();
+    crate::periodic_gc::init();
+    crate::gc_event_log::init();
+    crate::trace_recorder::init();
 }
 
 #[no_mangle]
@@ -124,22 +129,53 @@ pub extern "C" fn openjdk_is_gc_initialized() -> bool {
     crate::MMTK_INITIALIZED.load(std::sync::atomic::Ordering::SeqCst)
 }
 
+/// Set the minimum and maximum heap size in bytes.  Must be called before MMTk is created.  If
+/// `MMTK_RUNTIME_HEAP_LIMITS=1`, the binding's GC trigger is used, so that the limits can be
+/// changed at run time.
 #[no_mangle]
 pub extern "C" fn mmtk_set_heap_size(min: usize, max: usize) -> bool {
     use mmtk::util::options::GCTriggerSelector;
-    let mut builder = BUILDER.lock().unwrap();
-    let policy = if min == max {
+    if !crate::gc_trigger::set_heap_size(min, max) {
+        return false;
+    }
+    let policy = if crate::gc_trigger::runtime_limits_enabled() {
+        GCTriggerSelector::Delegated
+    } else if min == max {
         GCTriggerSelector::FixedHeapSize(min)
     } else {
         GCTriggerSelector::DynamicHeapSize(min, max)
     };
-    builder.options.gc_trigger.set(policy)
+    BUILDER.lock().unwrap().options.gc_trigger.set(policy)
+}
+
+/// Set HotSpot's `MinHeapFreeRatio` and `MaxHeapFreeRatio` for resizing the heap after GCs.
+#[no_mangle]
+pub extern "C" fn mmtk_set_heap_free_ratios(min: usize, max: usize) {
+    crate::gc_trigger::set_heap_free_ratios(min, max)
+}
+
+/// Change the soft and hard heap limits in bytes of the live MMTk instance.  Return false if the
+/// limits are invalid, i.e. not `min <= soft <= hard <= max` for the heap size set by
+/// `mmtk_set_heap_size`, or if `MMTK_RUNTIME_HEAP_LIMITS` is not enabled.
+#[no_mangle]
+pub extern "C" fn mmtk_set_heap_limits(soft: usize, hard: usize) -> bool {
+    crate::gc_trigger::set_heap_limits(soft, hard)
+}
+
+/// The current soft heap limit in bytes.
+#[no_mangle]
+pub extern "C" fn mmtk_soft_max_heap_size() -> usize {
+    crate::gc_trigger::soft_max_heap_bytes()
 }
 
 #[no_mangle]
 pub extern "C" fn bind_mutator(tls: VMMutatorThread) -> *mut libc::c_void {
     with_singleton!(|singleton| {
-        Box::into_raw(memory_manager::bind_mutator(singleton, tls)) as *mut libc::c_void
+        let mutator = memory_manager::bind_mutator(singleton, tls);
+        let size = std::mem::size_of_val(&*mutator);
+        let mutator = Box::into_raw(mutator) as *mut libc::c_void;
+        probe!(mmtk_openjdk, bind_mutator, mutator as usize, size);
+        mutator
     })
 }
 
@@ -147,6 +183,7 @@ pub extern "C" fn bind_mutator(tls: VMMutatorThread) -> *mut libc::c_void {
 // It is fine we turn the pointer back to box, as we turned a boxed value to the raw pointer in bind_mutator()
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn destroy_mutator(mutator: *mut libc::c_void) {
+    probe!(mmtk_openjdk, destroy_mutator, mutator as usize);
     with_mutator!(|mutator| memory_manager::destroy_mutator(mutator))
 }
 
@@ -167,7 +204,13 @@ pub extern "C" fn alloc(
     offset: usize,
     allocator: AllocationSemantics,
 ) -> Address {
-    with_mutator!(|mutator| memory_manager::alloc(mutator, size, align, offset, allocator))
+    with_mutator!(|mutator| {
+        let result = memory_manager::alloc(mutator, size, align, offset, allocator);
+        if result.is_zero() {
+            crate::collection::report_failed_allocation(mutator, size, allocator);
+        }
+        result
+    })
 }
 
 #[no_mangle]
@@ -242,6 +285,28 @@ pub extern "C" fn handle_user_collection_request(tls: VMMutatorThread) {
     })
 }
 
+/// Request a GC on behalf of the VM.  The C++ part of the binding decides the behavior from
+/// HotSpot's `GCCause`.
+///
+/// * `cause`: The name of the `GCCause`.  Must be a static string.
+/// * `full_heap`: Collect the whole heap, instead of letting the plan decide.
+/// * `force`: Collect even if the MMTk option `ignore_system_gc` is set.  Requests from the VM
+///   pass false and let mmtk-core decide.
+#[no_mangle]
+pub extern "C" fn mmtk_handle_collection_request(
+    tls: VMMutatorThread,
+    cause: *const c_char,
+    full_heap: bool,
+    force: bool,
+) {
+    crate::collection::set_requested_gc_cause(cause);
+    with_singleton!(|singleton| {
+        singleton.handle_user_collection_request(tls, force, full_heap);
+    });
+    // If mmtk-core ignored the request, do not attribute the next GC to it.
+    crate::collection::clear_requested_gc_cause(cause);
+}
+
 #[no_mangle]
 pub extern "C" fn mmtk_enable_compressed_oops() {
     crate::slots::enable_compressed_oops()
@@ -284,7 +349,8 @@ pub extern "C" fn add_phantom_candidate(reff: ObjectReference) {
 // harness_begin() and harness_end()
 
 #[no_mangle]
-pub extern "C" fn harness_begin(_id: usize) {
+pub extern "C" fn harness_begin(id: usize) {
+    crate::harness::set_pending_id(id);
     unsafe { ((*UPCALLS).harness_begin)() };
 }
 
@@ -293,17 +359,29 @@ pub extern "C" fn mmtk_harness_begin_impl() {
     // Pass null as tls, OpenJDK binding does not rely on the tls value to block the current thread and do a GC
     with_singleton!(|singleton| {
         memory_manager::harness_begin(singleton, VMMutatorThread(VMThread::UNINITIALIZED));
+        crate::harness::begin(memory_manager::used_bytes(singleton));
     })
 }
 
 #[no_mangle]
-pub extern "C" fn harness_end(_id: usize) {
-    unsafe { ((*UPCALLS).harness_end)() };
+pub extern "C" fn harness_end(id: usize) {
+    unsafe { ((*UPCALLS).harness_end)(id) };
+}
+
+#[no_mangle]
+pub extern "C" fn mmtk_harness_end_impl(id: usize) {
+    with_singleton!(|singleton| {
+        memory_manager::harness_end(singleton);
+        crate::harness::end(id, memory_manager::used_bytes(singleton));
+    });
+    crate::trace_recorder::write_trace();
 }
 
+/// Print the harness summary and finish the trace.  Called by the VM when it exits.
 #[no_mangle]
-pub extern "C" fn mmtk_harness_end_impl() {
-    with_singleton!(|singleton| memory_manager::harness_end(singleton))
+pub extern "C" fn mmtk_shutdown() {
+    crate::harness::print_summary();
+    crate::trace_recorder::finish();
 }
 
 #[no_mangle]
@@ -475,12 +553,125 @@ pub extern "C" fn mmtk_object_probable_write(mutator: *mut libc::c_void, obj: Ob
 // finalization
 #[no_mangle]
 pub extern "C" fn add_finalizer(object: ObjectReference) {
+    crate::FINALIZABLE_OBJECTS_REGISTERED.fetch_add(1, Ordering::Relaxed);
     with_singleton!(|singleton| memory_manager::add_finalizer(singleton, object));
 }
 
+/// Count an object registered for finalization with `Finalizer.register()`.  With the
+/// `final_references` feature, finalizable objects are not registered with mmtk-core.
+#[cfg(feature = "final_references")]
+#[no_mangle]
+pub extern "C" fn mmtk_count_registered_finalizer() {
+    crate::FINALIZABLE_OBJECTS_REGISTERED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Keep a finalizable object until the finalizer thread registers it with
+/// `Finalizer.register()`.  Used when the VM cannot call into Java where the object is allocated.
+/// Return true if no other object was waiting, so the finalizer thread should be woken up.
+#[cfg(feature = "final_references")]
+#[no_mangle]
+pub extern "C" fn mmtk_defer_finalizer_registration(object: ObjectReference) -> bool {
+    let mut pending = crate::FINALIZERS_TO_REGISTER.lock().unwrap();
+    pending.push(object);
+    pending.len() == 1
+}
+
+/// Fill `buf` with at most `capacity` objects waiting to be registered with
+/// `Finalizer.register()`, and return the number of objects written.  Return 0 if `buf` is null.
+///
+/// Once returned, the objects are no longer kept by MMTk.  The caller must keep them alive (e.g.
+/// with handles) before the next GC.
+///
+/// # Safety
+/// Caller needs to make sure `buf` is null or points to at least `capacity` writable elements.
+#[cfg(feature = "final_references")]
+#[no_mangle]
+pub unsafe extern "C" fn mmtk_take_finalizers_to_register(
+    buf: *mut ObjectReference,
+    capacity: usize,
+) -> usize {
+    if buf.is_null() {
+        return 0;
+    }
+    let buf = std::slice::from_raw_parts_mut(buf, capacity);
+    let mut pending = crate::FINALIZERS_TO_REGISTER.lock().unwrap();
+    let count = capacity.min(pending.len());
+    for (slot, object) in buf.iter_mut().zip(pending.drain(..count)) {
+        *slot = object;
+    }
+    count
+}
+
 #[no_mangle]
 pub extern "C" fn get_finalized_object() -> NullableObjectReference {
-    with_singleton!(|singleton| memory_manager::get_finalized_object(singleton).into())
+    let object = crate::READY_FOR_FINALIZATION.lock().unwrap().pop();
+    if object.is_some() {
+        crate::FINALIZABLE_OBJECTS_FINALIZED.fetch_add(1, Ordering::Relaxed);
+    }
+    object.into()
+}
+
+/// Fill `buf` with at most `capacity` objects that are ready to be finalized, and return the
+/// number of objects written.  This takes the lock once for the whole batch.  Return 0 if `buf` is
+/// null.
+///
+/// Once returned, the objects are no longer known to MMTk.  The caller must keep them alive (e.g.
+/// with handles) before the next GC.
+///
+/// # Safety
+/// Caller needs to make sure `buf` is null or points to at least `capacity` writable elements.
+#[no_mangle]
+pub unsafe extern "C" fn mmtk_get_finalized_objects(
+    buf: *mut ObjectReference,
+    capacity: usize,
+) -> usize {
+    if buf.is_null() {
+        return 0;
+    }
+    let buf = std::slice::from_raw_parts_mut(buf, capacity);
+    let mut ready = crate::READY_FOR_FINALIZATION.lock().unwrap();
+    let count = capacity.min(ready.len());
+    let start = ready.len() - count;
+    for (slot, object) in buf.iter_mut().zip(ready.drain(start..)) {
+        *slot = object;
+    }
+    crate::FINALIZABLE_OBJECTS_FINALIZED.fetch_add(count, Ordering::Relaxed);
+    count
+}
+
+/// Take all objects that are ready for finalization from mmtk-core.  Called at the end of a GC.
+/// mmtk-core hands them out one at a time, but mutators are stopped, so its lock is uncontended.
+pub(crate) fn take_ready_for_finalization<const COMPRESSED: bool>() {
+    let mmtk = crate::singleton::<COMPRESSED>();
+    let mut ready = crate::READY_FOR_FINALIZATION.lock().unwrap();
+    while let Some(object) = memory_manager::get_finalized_object(mmtk) {
+        ready.push(object);
+    }
+}
+
+/// Counters of finalizable objects since the VM started.  With the `final_references` feature,
+/// objects are counted when registered with `Finalizer.register()` and when their
+/// `FinalReference` is enqueued.
+#[repr(C)]
+pub struct FinalizationStats {
+    /// Objects registered for finalization.
+    pub registered: usize,
+    /// Registered objects not yet handed to the VM to be finalized.  They are either still
+    /// reachable, or waiting for the finalizer thread.
+    pub pending: usize,
+    /// Objects handed to the VM to be finalized.
+    pub finalized: usize,
+}
+
+#[no_mangle]
+pub extern "C" fn mmtk_get_finalization_stats() -> FinalizationStats {
+    let registered = crate::FINALIZABLE_OBJECTS_REGISTERED.load(Ordering::Relaxed);
+    let finalized = crate::FINALIZABLE_OBJECTS_FINALIZED.load(Ordering::Relaxed);
+    FinalizationStats {
+        registered,
+        pending: registered.saturating_sub(finalized),
+        finalized,
+    }
 }
 
 thread_local! {
@@ -503,6 +694,7 @@ pub extern "C" fn mmtk_add_nmethod_oop(addr: Address) {
 #[no_mangle]
 pub extern "C" fn mmtk_register_nmethod(nm: Address) {
     NMETHOD_SLOTS.with_borrow_mut(|slots| {
+        probe!(mmtk_openjdk, register_nmethod, nm.as_usize(), slots.len());
         if !slots.is_empty() {
             let mut roots = crate::NURSERY_CODE_CACHE_ROOTS.lock().unwrap();
             roots.insert(nm, std::mem::take(slots));
@@ -513,12 +705,226 @@ pub extern "C" fn mmtk_register_nmethod(nm: Address) {
 /// Unregister an nmethod.
 #[no_mangle]
 pub extern "C" fn mmtk_unregister_nmethod(nm: Address) {
-    {
-        let mut roots = crate::NURSERY_CODE_CACHE_ROOTS.lock().unwrap();
-        roots.remove(&nm);
+    let nursery = crate::NURSERY_CODE_CACHE_ROOTS.lock().unwrap().remove(&nm);
+    let mature = crate::MATURE_CODE_CACHE_ROOTS.lock().unwrap().remove(&nm);
+    let slots = nursery.or(mature).map_or(0, |roots| roots.len());
+    probe!(mmtk_openjdk, unregister_nmethod, nm.as_usize(), slots);
+}
+
+/// Return true if `object` has been reached in the current GC.  Only valid while weak references
+/// are processed, e.g. when an nmethod is unloaded.
+#[no_mangle]
+pub extern "C" fn mmtk_is_reachable(object: ObjectReference) -> bool {
+    object.is_reachable()
+}
+
+/// Report an nmethod that has frames on a thread stack.
+///
+/// The C++ part of the binding calls this while scanning thread stacks.  Oops in nmethods are
+/// weak in full-heap GCs, but nmethods on the stack must be kept alive.
+#[no_mangle]
+pub extern "C" fn mmtk_report_on_stack_nmethod(nm: Address) {
+    crate::ON_STACK_NMETHODS.lock().unwrap().insert(nm);
+}
+
+/// Return true if the current plan can pin objects.  Copying spaces (such as the nurseries of
+/// GenCopy and GenImmix) and compacting plans cannot.
+fn plan_can_pin() -> bool {
+    use mmtk::util::options::PlanSelector;
+    with_singleton!(|singleton| {
+        !matches!(
+            *singleton.get_options().plan,
+            PlanSelector::SemiSpace
+                | PlanSelector::GenCopy
+                | PlanSelector::GenImmix
+                | PlanSelector::MarkCompact
+                | PlanSelector::Compressor
+        )
+    })
+}
+
+/// Return true if objects can be pinned with the current plan.
+#[cfg(feature = "object_pinning")]
+#[no_mangle]
+pub extern "C" fn mmtk_supports_object_pinning() -> bool {
+    plan_can_pin()
+}
+
+/// Pin an object so that GC will not move it.  Pins are counted, and the object stays pinned
+/// until each `mmtk_pin_object` is matched by a `mmtk_unpin_object`.
+/// Return true if the object was not pinned before.
+#[cfg(feature = "object_pinning")]
+#[no_mangle]
+pub extern "C" fn mmtk_pin_object(object: ObjectReference) -> bool {
+    let mut pin_counts = crate::pin_counts(object).lock().unwrap();
+    let count = pin_counts.entry(object).or_insert(0);
+    *count += 1;
+    *count == 1 && memory_manager::pin_object(object)
+}
+
+/// Undo a `mmtk_pin_object`.  Return true if the object is no longer pinned.  Return false if the
+/// object was not pinned by `mmtk_pin_object`, which means pins and unpins are unbalanced.
+#[cfg(feature = "object_pinning")]
+#[no_mangle]
+pub extern "C" fn mmtk_unpin_object(object: ObjectReference) -> bool {
+    let mut pin_counts = crate::pin_counts(object).lock().unwrap();
+    let Some(count) = pin_counts.get_mut(&object) else {
+        log::warn!("Unpinning {:?}, which is not pinned", object);
+        return false;
+    };
+    *count -= 1;
+    if *count > 0 {
+        return false;
     }
-    {
-        let mut roots = crate::MATURE_CODE_CACHE_ROOTS.lock().unwrap();
-        roots.remove(&nm);
+    pin_counts.remove(&object);
+    memory_manager::unpin_object(object)
+}
+
+#[cfg(feature = "object_pinning")]
+#[no_mangle]
+pub extern "C" fn mmtk_is_pinned(object: ObjectReference) -> bool {
+    memory_manager::is_pinned(object)
+}
+
+/// Return true if the current plan can process pinning roots, which conservative stack scanning
+/// and the JNI handles of threads in critical regions report.
+#[no_mangle]
+pub extern "C" fn mmtk_supports_pinning_roots() -> bool {
+    plan_can_pin()
+}
+
+/// Get the reference processing statistics of the last GC.
+#[no_mangle]
+pub extern "C" fn mmtk_get_reference_stats() -> crate::reference_stats::ReferenceProcessingStats {
+    crate::reference_stats::last_gc_stats()
+}
+
+/// Enable HotSpot's GC overhead limit (`UseGCOverheadLimit`).  The arguments are the values of
+/// `GCTimeLimit`, `GCHeapFreeLimit` and `AdaptiveSizePolicyGCTimeLimitThreshold`.
+#[no_mangle]
+pub extern "C" fn mmtk_set_gc_overhead_limit(
+    gc_time_limit: usize,
+    gc_heap_free_limit: usize,
+    threshold: usize,
+) {
+    crate::gc_stats::set_gc_overhead_limit(gc_time_limit, gc_heap_free_limit, threshold);
+}
+
+/// Return true if the GC overhead limit has been exceeded since the last call.  The allocation
+/// that observes it should fail with "GC overhead limit exceeded".
+#[no_mangle]
+pub extern "C" fn mmtk_gc_overhead_limit_exceeded() -> bool {
+    crate::gc_stats::take_gc_overhead_limit_exceeded()
+}
+
+/// The results of the last GC.
+#[no_mangle]
+pub extern "C" fn mmtk_get_last_gc_result() -> crate::gc_stats::GCResult {
+    crate::gc_stats::last_gc_result()
+}
+
+/// The interval of periodic GCs in milliseconds, or 0 if periodic GC is disabled.
+#[no_mangle]
+pub extern "C" fn mmtk_periodic_gc_interval() -> u64 {
+    crate::periodic_gc::interval_ms()
+}
+
+/// Return true if the periodic GC thread should trigger a GC now.
+#[no_mangle]
+pub extern "C" fn mmtk_should_trigger_periodic_gc() -> bool {
+    crate::periodic_gc::should_trigger()
+}
+
+/// Return free memory to the OS.  Called by the periodic GC thread after a periodic GC.
+#[no_mangle]
+pub extern "C" fn mmtk_release_free_memory() {
+    crate::periodic_gc::release_free_memory()
+}
+
+/// Call `callback` for each object in the MMTk heap, passing `data` back as the last argument.
+/// Objects are found with the VO bit.  Must be called when mutators are stopped and no GC is in
+/// progress, e.g. in a VM operation at a safepoint.
+#[cfg(feature = "vo_bit")]
+#[no_mangle]
+pub extern "C" fn mmtk_enumerate_objects(
+    callback: extern "C" fn(object: ObjectReference, data: *mut libc::c_void),
+    data: *mut libc::c_void,
+) {
+    with_singleton!(|singleton| singleton.enumerate_objects(|object| callback(object, data)))
+}
+
+/// Return true if `addr` is the address of an object in the MMTk heap, according to the VO bit.
+#[cfg(feature = "vo_bit")]
+#[no_mangle]
+pub extern "C" fn mmtk_is_object(addr: Address) -> bool {
+    !addr.is_zero() && memory_manager::is_mmtk_object(addr).is_some()
+}
+
+/// Find the object that contains `addr`, which may point into the middle of the object.  Return
+/// the address of the object, or null if `addr` is not inside any object.
+#[cfg(feature = "vo_bit")]
+#[no_mangle]
+pub extern "C" fn mmtk_find_object_from_internal_pointer(addr: Address) -> Address {
+    if addr.is_zero() {
+        return Address::ZERO;
     }
+    // Objects larger than this are allocated in the large object space.
+    let max_search_bytes = get_max_non_los_default_alloc_bytes();
+    let object = if crate::use_compressed_oops() {
+        memory_manager::find_object_from_internal_pointer::<OpenJDK<true>>(addr, max_search_bytes)
+    } else {
+        memory_manager::find_object_from_internal_pointer::<OpenJDK<false>>(addr, max_search_bytes)
+    };
+    object.map_or(Address::ZERO, |object| object.to_raw_address())
+}
+
+/// Print the heap into `buf` as a NUL-terminated string of at most `capacity` bytes (including
+/// the NUL), and return the length of the string.  The output is truncated if it does not fit.
+/// This does not allocate memory or block on locks, so it can be called while reporting a crash.
+///
+/// The output does not include the states of GC threads, because mmtk-core does not expose the
+/// states of its workers.  `MMTkHeap::print_gc_threads_on` prints the GC threads as HotSpot
+/// threads instead.
+///
+/// # Safety
+/// Caller needs to make sure `buf` points to at least `capacity` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mmtk_print_heap_info(buf: *mut c_char, capacity: usize) -> usize {
+    if buf.is_null() {
+        return 0;
+    }
+    let buf = std::slice::from_raw_parts_mut(buf as *mut u8, capacity);
+    crate::heap_info::print_heap_info(buf)
+}
+
+/// The number of MMTk spaces.  Each space is reported as a memory pool.
+#[no_mangle]
+pub extern "C" fn mmtk_num_spaces() -> usize {
+    crate::space_usage::num_spaces()
+}
+
+/// Write the usage of the space at `index` into `usage`.  Return false if there is no such
+/// space.
+///
+/// # Safety
+/// Caller needs to make sure `usage` points to a writable `SpaceUsage`.
+#[no_mangle]
+pub unsafe extern "C" fn mmtk_get_space_usage(
+    index: usize,
+    usage: *mut crate::space_usage::SpaceUsage,
+) -> bool {
+    match crate::space_usage::space_usage(index) {
+        Some(result) => {
+            *usage = result;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Return true if the plan is generational.  Nursery GCs of a generational plan are reported by a
+/// separate memory manager.
+#[no_mangle]
+pub extern "C" fn mmtk_is_generational_plan() -> bool {
+    with_singleton!(|singleton| singleton.get_plan().generational().is_some())
 }
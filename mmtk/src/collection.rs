@@ -1,32 +1,171 @@
+use libc::c_char;
 use mmtk::util::alloc::AllocationError;
+use mmtk::util::heap::GCTriggerPolicy;
 use mmtk::util::opaque_pointer::*;
+use mmtk::vm::slot::Slot;
 use mmtk::vm::{Collection, GCThreadContext};
-use mmtk::Mutator;
+use mmtk::{AllocationSemantics, Mutator, MutatorContext};
+use std::cell::Cell;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::time::Instant;
 
+use crate::gc_stats::GCResult;
 use crate::UPCALLS;
-use crate::{MutatorClosure, OpenJDK};
+use crate::{MutatorClosure, OpenJDK, OpenJDKSlot};
 
 pub struct VMCollection {}
 
 const GC_THREAD_KIND_WORKER: libc::c_int = 1;
 
+/// The name of HotSpot's `GCCause::_allocation_failure`.  Used for GCs triggered by MMTk.
+const ALLOCATION_FAILURE_CAUSE: &[u8] = b"Allocation Failure\0";
+
+/// The cause of the GC last requested by the VM.  Taken when the GC starts.
+///
+/// There is only one slot, so the last writer wins: if several threads request a GC with
+/// different causes before it starts, the GC (which serves all of them) reports the cause of
+/// whichever request was stored last.
+static REQUESTED_GC_CAUSE: AtomicPtr<c_char> = AtomicPtr::new(null_mut());
+/// The cause of the current (or the last) GC.
+static CURRENT_GC_CAUSE: AtomicPtr<c_char> = AtomicPtr::new(null_mut());
+
+/// Remember the cause of a GC requested by the VM.  `cause` must be a static string.
+pub fn set_requested_gc_cause(cause: *const c_char) {
+    REQUESTED_GC_CAUSE.store(cause as *mut c_char, Ordering::SeqCst);
+}
+
+/// Forget the requested cause if no GC has taken it.
+pub fn clear_requested_gc_cause(cause: *const c_char) {
+    let _ = REQUESTED_GC_CAUSE.compare_exchange(
+        cause as *mut c_char,
+        null_mut(),
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+    );
+}
+
+/// The cause of the current (or the last) GC, as a NUL-terminated string.
+pub fn current_gc_cause() -> *const c_char {
+    let cause = CURRENT_GC_CAUSE.load(Ordering::SeqCst);
+    if cause.is_null() {
+        ALLOCATION_FAILURE_CAUSE.as_ptr() as *const c_char
+    } else {
+        cause
+    }
+}
+
+/// Details of an allocation failure.  Must match `OutOfMemoryReport` in `mmtk.h`.
+#[repr(C)]
+pub struct OutOfMemoryReport {
+    /// The size of the allocation request that failed.  0 if MMTk ran out of memory when not
+    /// allocating for a mutator.
+    pub requested_bytes: usize,
+    /// The allocation semantics of the failed request, which decides the space to allocate into.
+    pub semantics: AllocationSemantics,
+    pub used_bytes: usize,
+    pub free_bytes: usize,
+    pub total_bytes: usize,
+    pub max_heap_bytes: usize,
+    /// The results of the last GC.
+    pub last_gc: GCResult,
+}
+
+thread_local! {
+    /// Set when an allocation of the current mutator ran out of heap.  The VM is told once the
+    /// allocation returns, by `report_failed_allocation`, which knows the request.
+    static HEAP_OUT_OF_MEMORY: Cell<bool> = const { Cell::new(false) };
+}
+
+fn out_of_memory_report<const COMPRESSED: bool>(
+    requested_bytes: usize,
+    semantics: AllocationSemantics,
+) -> OutOfMemoryReport {
+    let mmtk = crate::singleton::<COMPRESSED>();
+    OutOfMemoryReport {
+        requested_bytes,
+        semantics,
+        used_bytes: mmtk::memory_manager::used_bytes(mmtk),
+        free_bytes: mmtk::memory_manager::free_bytes(mmtk),
+        total_bytes: mmtk::memory_manager::total_bytes(mmtk),
+        max_heap_bytes: crate::gc_trigger::max_heap_bytes(),
+        last_gc: crate::gc_stats::last_gc_result(),
+    }
+}
+
+/// Called when an allocation of `size` bytes with `semantics` by `mutator` returned null.  Report
+/// it to the VM if the allocation ran out of heap.
+pub fn report_failed_allocation<const COMPRESSED: bool>(
+    mutator: &Mutator<OpenJDK<COMPRESSED>>,
+    size: usize,
+    semantics: AllocationSemantics,
+) {
+    if HEAP_OUT_OF_MEMORY.replace(false) {
+        let report = out_of_memory_report::<COMPRESSED>(size, semantics);
+        unsafe {
+            ((*UPCALLS).out_of_memory)(
+                mutator.get_tls().0,
+                AllocationError::HeapOutOfMemory,
+                &report,
+            );
+        }
+    }
+}
+
 impl<const COMPRESSED: bool> Collection<OpenJDK<COMPRESSED>> for VMCollection {
     fn stop_all_mutators<F>(tls: VMWorkerThread, mut mutator_visitor: F)
     where
         F: FnMut(&'static mut Mutator<OpenJDK<COMPRESSED>>),
     {
-        unsafe {
-            ((*UPCALLS).stop_all_mutators)(
-                tls,
-                MutatorClosure::from_rust_closure::<_, COMPRESSED>(&mut mutator_visitor),
-            );
+        let stopping = Instant::now();
+        let mut num_mutators = 0usize;
+        {
+            let _span = crate::trace_recorder::span("StopMutators", "stw");
+            probe!(mmtk_openjdk, stop_mutators_begin);
+            let mut visit_mutator = |mutator| {
+                num_mutators += 1;
+                mutator_visitor(mutator);
+            };
+            unsafe {
+                ((*UPCALLS).stop_all_mutators)(
+                    tls,
+                    MutatorClosure::from_rust_closure::<_, COMPRESSED>(&mut visit_mutator),
+                );
+            }
+            probe!(mmtk_openjdk, stop_mutators_end, num_mutators);
+        }
+        crate::gc_event_log::record_stop_mutators(stopping.elapsed());
+        let cause = REQUESTED_GC_CAUSE.swap(null_mut(), Ordering::SeqCst);
+        CURRENT_GC_CAUSE.store(cause, Ordering::SeqCst);
+        probe!(mmtk_openjdk, gc_cause, current_gc_cause() as usize);
+        crate::gc_stats::gc_start::<COMPRESSED>();
+        if crate::trace_recorder::is_enabled() {
+            crate::trace_recorder::async_begin("GC", "gc", crate::gc_stats::current_gc_index());
         }
+        crate::reference_glue::setup_soft_ref_policy::<COMPRESSED>();
+        #[cfg(feature = "final_references")]
+        crate::reference_processor::enable_discovery();
     }
 
     fn resume_mutators(tls: VMWorkerThread) {
-        unsafe {
-            ((*UPCALLS).resume_mutators)(tls);
+        crate::api::take_ready_for_finalization::<COMPRESSED>();
+        crate::reference_glue::record_used_bytes_after_gc::<COMPRESSED>();
+        crate::reference_stats::finish_gc();
+        crate::root_stats::finish_gc();
+        crate::gc_stats::gc_end::<COMPRESSED>();
+        let resuming = Instant::now();
+        {
+            let _span = crate::trace_recorder::span("ResumeMutators", "stw");
+            probe!(mmtk_openjdk, resume_mutators_begin);
+            unsafe {
+                ((*UPCALLS).resume_mutators)(tls);
+            }
+            probe!(mmtk_openjdk, resume_mutators_end);
+        }
+        if crate::trace_recorder::is_enabled() {
+            crate::trace_recorder::async_end("GC", "gc", crate::gc_stats::current_gc_index());
         }
+        crate::gc_event_log::log_gc::<COMPRESSED>(resuming.elapsed());
     }
 
     fn block_for_gc(_tls: VMMutatorThread) {
@@ -47,14 +186,68 @@ impl<const COMPRESSED: bool> Collection<OpenJDK<COMPRESSED>> for VMCollection {
     }
 
     fn out_of_memory(tls: VMThread, err_kind: AllocationError) {
+        // mmtk-core only gets here after its own last-ditch attempt: when an allocation still
+        // fails after a GC, it retries with an emergency collection, which collects the whole heap
+        // and clears all soft references (see `setup_soft_ref_policy`).
+        // So the binding does not trigger another GC before reporting out of memory.
+        // A mutator running out of heap is reported when its allocation returns, so that the
+        // allocation path does not have to record every request for the report.
+        if matches!(err_kind, AllocationError::HeapOutOfMemory)
+            && unsafe { ((*UPCALLS).is_mutator)(tls) }
+        {
+            HEAP_OUT_OF_MEMORY.set(true);
+            return;
+        }
+        let report = out_of_memory_report::<COMPRESSED>(0, AllocationSemantics::Default);
         unsafe {
-            ((*UPCALLS).out_of_memory)(tls, err_kind);
+            ((*UPCALLS).out_of_memory)(tls, err_kind, &report);
         }
     }
 
     fn schedule_finalization(_tls: VMWorkerThread) {
+        let stats = crate::api::mmtk_get_finalization_stats();
+        probe!(
+            mmtk_openjdk,
+            schedule_finalizer,
+            stats.registered,
+            stats.pending,
+            stats.finalized
+        );
         unsafe {
             ((*UPCALLS).schedule_finalizer)();
         }
     }
+
+    fn create_gc_trigger() -> Box<dyn GCTriggerPolicy<OpenJDK<COMPRESSED>>> {
+        Box::new(crate::gc_trigger::OpenJDKGCTrigger)
+    }
+
+    fn post_forwarding(_tls: VMWorkerThread) {
+        // Find nmethods that had embedded oops updated, and let the VM fix up their relocations
+        // and flush the instruction cache.
+        let snapshots =
+            std::mem::take(&mut *crate::CODE_CACHE_ROOTS_BEFORE_FORWARDING.lock().unwrap());
+        if snapshots.is_empty() {
+            return;
+        }
+        let moved_nmethods: Vec<_> = {
+            let mature = crate::MATURE_CODE_CACHE_ROOTS.lock().unwrap();
+            snapshots
+                .into_iter()
+                .filter(|(nm, old_values)| {
+                    mature.get(nm).is_some_and(|roots| {
+                        roots.iter().zip(old_values).any(|(root, old_value)| {
+                            OpenJDKSlot::<COMPRESSED>::from(*root).load() != *old_value
+                        })
+                    })
+                })
+                .map(|(nm, _)| nm)
+                .collect()
+        };
+        for nm in moved_nmethods {
+            unsafe {
+                ((*UPCALLS).fix_nmethod_relocations)(nm);
+            }
+        }
+    }
 }
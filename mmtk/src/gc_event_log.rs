@@ -0,0 +1,165 @@
+//! A machine-readable log with one JSON object per line for each GC.
+//!
+//! Enabled by the environment variable `MMTK_GC_EVENT_LOG=<path>`.  Lines are appended to the
+//! file, so the log of several runs can be collected in one file.  Each line has:
+//!
+//! *   `gc`, `cause`, `plan`, `kind` (`"nursery"` or `"full"`) and `timestamp_ms` (the end of the
+//!     GC, since the Unix epoch).
+//! *   `pause_ns`: the time to stop mutators, the collection with mutators stopped, the time to
+//!     resume mutators, and their total.  `mutator_ns` is the time since the previous GC.
+//! *   `heap`: the used bytes before and after the GC, and the total heap size.
+//! *   `spaces`: the used bytes of each space before and after the GC.
+//! *   `roots`: the number of root slots of each category.  Conservative stack roots are counted as
+//!     objects.
+//! *   `references`: the reference processing statistics of each reference type.
+//! *   `finalization`: the counts of finalizable objects since the VM started.
+
+use crate::reference_stats::ReferenceStats;
+use crate::root_stats::ROOT_CATEGORY_NAMES;
+use std::ffi::CStr;
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+/// The time to stop mutators in the current GC.
+static STOP_MUTATORS_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Open the log file if `MMTK_GC_EVENT_LOG` is set.  If the file cannot be opened, the log is
+/// disabled.
+pub fn init() {
+    let Some(path) = std::env::var_os("MMTK_GC_EVENT_LOG") else {
+        return;
+    };
+    let file = match OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Failed to open the GC event log {:?}: {}", path, e);
+            return;
+        }
+    };
+    *LOG_FILE.lock().unwrap() = Some(file);
+}
+
+/// Record the time it took to stop mutators for the current GC.
+pub fn record_stop_mutators(duration: Duration) {
+    STOP_MUTATORS_NS.store(duration.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Append the event of the GC that just finished.  Called after mutators are resumed, which took
+/// `resume_mutators`.
+pub fn log_gc<const COMPRESSED: bool>(resume_mutators: Duration) {
+    let mut file = LOG_FILE.lock().unwrap();
+    let Some(file) = file.as_mut() else {
+        return;
+    };
+    let mut line = String::new();
+    write_event::<COMPRESSED>(&mut line, resume_mutators.as_nanos() as u64).unwrap();
+    line.push('\n');
+    // A failure to write the log should not crash the VM.
+    let _ = file.write_all(line.as_bytes());
+}
+
+fn write_event<const COMPRESSED: bool>(
+    w: &mut String,
+    resume_mutators_ns: u64,
+) -> std::fmt::Result {
+    let mmtk = crate::singleton::<COMPRESSED>();
+    let gc = crate::gc_stats::last_gc_result();
+    let cause = unsafe { CStr::from_ptr(crate::collection::current_gc_cause()) };
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_millis());
+    let stop_mutators_ns = STOP_MUTATORS_NS.load(Ordering::Relaxed);
+
+    write!(w, "{{\"gc\":{},\"cause\":", gc.index)?;
+    write_json_string(w, &cause.to_string_lossy())?;
+    write!(
+        w,
+        ",\"plan\":\"{:?}\",\"kind\":\"{}\",\"timestamp_ms\":{}",
+        *mmtk.get_options().plan,
+        if gc.full_heap { "full" } else { "nursery" },
+        timestamp_ms,
+    )?;
+    write!(
+        w,
+        ",\"pause_ns\":{{\"stop_mutators\":{},\"collection\":{},\"resume_mutators\":{},\"total\":{}}}",
+        stop_mutators_ns,
+        gc.pause_ns,
+        resume_mutators_ns,
+        stop_mutators_ns + gc.pause_ns + resume_mutators_ns,
+    )?;
+    write!(
+        w,
+        ",\"mutator_ns\":{},\"heap\":{{\"used_before\":{},\"used_after\":{},\"total\":{}}}",
+        gc.mutator_ns, gc.used_bytes_before, gc.used_bytes_after, gc.total_bytes,
+    )?;
+
+    w.push_str(",\"spaces\":[");
+    for (i, (before, after)) in crate::gc_stats::last_gc_space_usage().iter().enumerate() {
+        if i != 0 {
+            w.push(',');
+        }
+        w.push_str("{\"name\":");
+        write_json_string(w, before.name())?;
+        write!(
+            w,
+            ",\"used_before\":{},\"used_after\":{}}}",
+            before.used_bytes, after.used_bytes
+        )?;
+    }
+    w.push(']');
+
+    w.push_str(",\"roots\":{");
+    let roots = crate::root_stats::last_gc_counts();
+    for (i, (name, count)) in ROOT_CATEGORY_NAMES.iter().zip(roots.iter()).enumerate() {
+        if i != 0 {
+            w.push(',');
+        }
+        write!(w, "\"{}\":{}", name, count)?;
+    }
+    w.push('}');
+
+    let references = crate::reference_stats::last_gc_stats();
+    w.push_str(",\"references\":{");
+    write_reference_stats(w, "soft", &references.soft)?;
+    w.push(',');
+    write_reference_stats(w, "weak", &references.weak)?;
+    w.push(',');
+    write_reference_stats(w, "final", &references.final_)?;
+    w.push(',');
+    write_reference_stats(w, "phantom", &references.phantom)?;
+    w.push('}');
+
+    let finalization = crate::api::mmtk_get_finalization_stats();
+    write!(
+        w,
+        ",\"finalization\":{{\"registered\":{},\"pending\":{},\"finalized\":{}}}}}",
+        finalization.registered, finalization.pending, finalization.finalized,
+    )
+}
+
+fn write_reference_stats(w: &mut String, name: &str, stats: &ReferenceStats) -> std::fmt::Result {
+    write!(
+        w,
+        "\"{}\":{{\"discovered\":{},\"cleared\":{},\"retained\":{},\"enqueued\":{}}}",
+        name, stats.discovered, stats.cleared, stats.retained, stats.enqueued,
+    )
+}
+
+fn write_json_string(w: &mut String, s: &str) -> std::fmt::Result {
+    w.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => w.push_str("\\\""),
+            '\\' => w.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(w, "\\u{:04x}", c as u32)?,
+            c => w.push(c),
+        }
+    }
+    w.push('"');
+    Ok(())
+}
@@ -0,0 +1,273 @@
+//! Per-GC results, GC notifications for the VM, and HotSpot's GC overhead limit.
+//!
+//! The VM is notified when each GC starts and ends, with the usage of each space before and after
+//! the GC, so that `GarbageCollectorMXBean` can send `GarbageCollectionNotificationInfo`.
+//!
+//! Like `UseGCOverheadLimit` in ParallelGC, the GC overhead limit is exceeded if, after
+//! `AdaptiveSizePolicyGCTimeLimitThreshold` consecutive full-heap GCs, the (decaying) average
+//! fraction of time spent in GC is over `GCTimeLimit` percent, and less than `GCHeapFreeLimit`
+//! percent of the heap is free.  The next allocation then fails with "GC overhead limit
+//! exceeded".
+
+use crate::space_usage::SpaceUsage;
+use crate::UPCALLS;
+use libc::c_char;
+use mmtk::memory_manager;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The results of a GC.  Must match `GCResult` in `mmtk.h`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GCResult {
+    /// The number of GCs so far, including this one.  0 if there has been no GC.
+    pub index: usize,
+    /// The GC collected the whole heap.
+    pub full_heap: bool,
+    /// The time from stopping to resuming mutators.
+    pub pause_ns: u64,
+    /// The time from the end of the previous GC (or the start of the VM) to the start of this GC.
+    pub mutator_ns: u64,
+    pub used_bytes_before: usize,
+    pub used_bytes_after: usize,
+    pub total_bytes: usize,
+}
+
+impl GCResult {
+    pub fn freed_bytes(&self) -> usize {
+        self.used_bytes_before.saturating_sub(self.used_bytes_after)
+    }
+}
+
+/// The start or the end of a GC, reported to the VM.  Must match `GCNotification` in `mmtk.h`.
+#[repr(C)]
+pub struct GCNotification {
+    /// The index of the GC, as `GCResult::index`.
+    pub id: usize,
+    /// The cause of the GC as a NUL-terminated string.
+    pub cause: *const c_char,
+    pub full_heap: bool,
+    /// The time from stopping to resuming mutators.  0 when the GC starts.
+    pub duration_ns: u64,
+    /// The number of spaces in `before` and `after`.
+    pub num_spaces: usize,
+    /// The usage of each space when the GC started.
+    pub before: *const SpaceUsage,
+    /// The usage of each space when the GC ended.  Null when the GC starts.
+    pub after: *const SpaceUsage,
+}
+
+/// The weight of the last GC in the average GC cost, as `AdaptiveSizePolicyWeight` in HotSpot.
+const GC_COST_WEIGHT: f64 = 0.1;
+
+struct State {
+    /// When the last GC ended, or when the VM started.
+    last_gc_end: Instant,
+    /// When the current GC started.
+    gc_start: Instant,
+    /// The results of the current GC so far.
+    current: GCResult,
+    /// The results of the last finished GC.
+    last: GCResult,
+    /// The decaying average of the fraction of time spent in GC.
+    avg_gc_cost: f64,
+    /// The usage of each space when the current (or the last) GC started.
+    spaces_before: Vec<SpaceUsage>,
+    /// The usage of each space when the last GC ended.
+    spaces_after: Vec<SpaceUsage>,
+}
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State {
+        last_gc_end: Instant::now(),
+        gc_start: Instant::now(),
+        current: GCResult::default(),
+        last: GCResult::default(),
+        avg_gc_cost: 0.0,
+        spaces_before: vec![],
+        spaces_after: vec![],
+    });
+}
+
+/// Check the GC overhead limit after full-heap GCs.  Set by `UseGCOverheadLimit`.
+static GC_OVERHEAD_LIMIT_ENABLED: AtomicBool = AtomicBool::new(false);
+/// `GCTimeLimit`, in percent.
+static GC_TIME_LIMIT: AtomicUsize = AtomicUsize::new(98);
+/// `GCHeapFreeLimit`, in percent.
+static GC_HEAP_FREE_LIMIT: AtomicUsize = AtomicUsize::new(2);
+/// `AdaptiveSizePolicyGCTimeLimitThreshold`.
+static GC_TIME_LIMIT_THRESHOLD: AtomicUsize = AtomicUsize::new(5);
+/// The number of consecutive full-heap GCs that were over both limits.
+static GC_OVERHEAD_LIMIT_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// Set when the limit is exceeded, and taken by the next allocation, which will fail.
+static GC_OVERHEAD_LIMIT_EXCEEDED: AtomicBool = AtomicBool::new(false);
+
+/// Start measuring the mutator time before the first GC.
+pub fn init() {
+    lazy_static::initialize(&STATE);
+}
+
+/// Enable the GC overhead limit with HotSpot's options.
+pub fn set_gc_overhead_limit(gc_time_limit: usize, gc_heap_free_limit: usize, threshold: usize) {
+    GC_TIME_LIMIT.store(gc_time_limit, Ordering::Relaxed);
+    GC_HEAP_FREE_LIMIT.store(gc_heap_free_limit, Ordering::Relaxed);
+    GC_TIME_LIMIT_THRESHOLD.store(threshold.max(1), Ordering::Relaxed);
+    GC_OVERHEAD_LIMIT_ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Return true (and reset the flag) if the GC overhead limit was exceeded since the last call.
+/// Checked by every allocation of the VM, so only swap when the flag is set.
+pub fn take_gc_overhead_limit_exceeded() -> bool {
+    GC_OVERHEAD_LIMIT_EXCEEDED.load(Ordering::Relaxed)
+        && GC_OVERHEAD_LIMIT_EXCEEDED.swap(false, Ordering::SeqCst)
+}
+
+/// Return true if the next full-heap GC over the limits will exceed the GC overhead limit.  Like
+/// HotSpot, we clear all soft references in that GC to give the application a last chance.
+pub fn is_near_gc_overhead_limit() -> bool {
+    GC_OVERHEAD_LIMIT_ENABLED.load(Ordering::Relaxed)
+        && GC_OVERHEAD_LIMIT_COUNT.load(Ordering::Relaxed) + 1
+            >= GC_TIME_LIMIT_THRESHOLD.load(Ordering::Relaxed)
+}
+
+fn spaces_usage() -> Vec<SpaceUsage> {
+    let mut spaces = vec![];
+    crate::space_usage::for_each_space_usage(|_, usage| spaces.push(usage));
+    spaces
+}
+
+/// Called when all mutators are stopped for a GC.
+pub fn gc_start<const COMPRESSED: bool>() {
+    let mmtk = crate::singleton::<COMPRESSED>();
+    let spaces_before = spaces_usage();
+    let result = {
+        let mut state = STATE.lock().unwrap();
+        let now = Instant::now();
+        let index = state.last.index + 1;
+        let mutator_ns = now.duration_since(state.last_gc_end).as_nanos() as u64;
+        state.gc_start = now;
+        state.current = GCResult {
+            index,
+            full_heap: !crate::gc_work::is_current_gc_nursery(mmtk),
+            mutator_ns,
+            used_bytes_before: memory_manager::used_bytes(mmtk),
+            ..Default::default()
+        };
+        state.spaces_before = spaces_before.clone();
+        state.current
+    };
+    notify(
+        unsafe { (*UPCALLS).notify_gc_start },
+        &result,
+        &spaces_before,
+        None,
+    );
+}
+
+/// Called before mutators are resumed after a GC.
+pub fn gc_end<const COMPRESSED: bool>() {
+    let mmtk = crate::singleton::<COMPRESSED>();
+    let spaces_after = spaces_usage();
+    let (result, spaces_before) = {
+        let mut state = STATE.lock().unwrap();
+        let now = Instant::now();
+        let mut result = state.current;
+        result.full_heap = !crate::gc_work::is_current_gc_nursery(mmtk);
+        result.pause_ns = now.duration_since(state.gc_start).as_nanos() as u64;
+        result.used_bytes_after = memory_manager::used_bytes(mmtk);
+        result.total_bytes = memory_manager::total_bytes(mmtk);
+
+        let elapsed_ns = result.pause_ns + result.mutator_ns;
+        if elapsed_ns > 0 {
+            let cost = result.pause_ns as f64 / elapsed_ns as f64;
+            state.avg_gc_cost = state.avg_gc_cost * (1.0 - GC_COST_WEIGHT) + cost * GC_COST_WEIGHT;
+        }
+        if result.full_heap {
+            check_gc_overhead_limit(&result, state.avg_gc_cost);
+        }
+
+        state.last = result;
+        state.last_gc_end = now;
+        state.spaces_after = spaces_after.clone();
+        (result, state.spaces_before.clone())
+    };
+    crate::harness::record_gc(&result);
+    notify(
+        unsafe { (*UPCALLS).notify_gc_end },
+        &result,
+        &spaces_before,
+        Some(&spaces_after),
+    );
+}
+
+/// Notify the VM of the start (if `after` is `None`) or the end of a GC.
+fn notify(
+    upcall: extern "C" fn(*const GCNotification),
+    result: &GCResult,
+    before: &[SpaceUsage],
+    after: Option<&[SpaceUsage]>,
+) {
+    // The spaces of a plan do not change.
+    debug_assert!(after.map_or(true, |after| after.len() == before.len()));
+    let notification = GCNotification {
+        id: result.index,
+        cause: crate::collection::current_gc_cause(),
+        full_heap: result.full_heap,
+        duration_ns: result.pause_ns,
+        num_spaces: before.len(),
+        before: before.as_ptr(),
+        after: after.map_or(std::ptr::null(), |after| after.as_ptr()),
+    };
+    upcall(&notification);
+}
+
+fn check_gc_overhead_limit(result: &GCResult, avg_gc_cost: f64) {
+    if !GC_OVERHEAD_LIMIT_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let free_bytes = result.total_bytes.saturating_sub(result.used_bytes_after);
+    let over_time_limit = avg_gc_cost * 100.0 > GC_TIME_LIMIT.load(Ordering::Relaxed) as f64;
+    let under_free_limit =
+        free_bytes * 100 < result.total_bytes * GC_HEAP_FREE_LIMIT.load(Ordering::Relaxed);
+    if !(over_time_limit && under_free_limit) {
+        GC_OVERHEAD_LIMIT_COUNT.store(0, Ordering::Relaxed);
+        return;
+    }
+    let count = GC_OVERHEAD_LIMIT_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    if count >= GC_TIME_LIMIT_THRESHOLD.load(Ordering::Relaxed) {
+        GC_OVERHEAD_LIMIT_COUNT.store(0, Ordering::Relaxed);
+        GC_OVERHEAD_LIMIT_EXCEEDED.store(true, Ordering::SeqCst);
+    }
+}
+
+/// The time since the last GC ended, or since the VM started if there has been no GC.
+pub fn time_since_last_gc() -> Duration {
+    STATE.lock().unwrap().last_gc_end.elapsed()
+}
+
+/// The results of the last finished GC.
+pub fn last_gc_result() -> GCResult {
+    STATE.lock().unwrap().last
+}
+
+/// The index of the current (or the last) GC.
+pub fn current_gc_index() -> usize {
+    STATE.lock().unwrap().current.index
+}
+
+/// The usage of each space before and after the last finished GC.
+pub fn last_gc_space_usage() -> Vec<(SpaceUsage, SpaceUsage)> {
+    let state = STATE.lock().unwrap();
+    state
+        .spaces_before
+        .iter()
+        .copied()
+        .zip(state.spaces_after.iter().copied())
+        .collect()
+}
+
+/// Like `last_gc_result`, but return `None` instead of blocking if the results are being updated.
+pub fn try_last_gc_result() -> Option<GCResult> {
+    STATE.try_lock().ok().map(|state| state.last)
+}
@@ -0,0 +1,154 @@
+//! The GC trigger of the binding, which allows the heap limits to be changed at run time.
+//!
+//! mmtk-core's own triggers take their heap sizes from the options when MMTk is created.  If
+//! changing the limits at run time is enabled with `MMTK_RUNTIME_HEAP_LIMITS=1`, this trigger is
+//! used instead (`GCTriggerSelector::Delegated`).  Otherwise, the heap size is fixed or dynamic as
+//! chosen by mmtk-core's triggers, and the limits below only record `-Xms` and `-Xmx`.
+//!
+//! This trigger has three limits:
+//!
+//! *   The minimum heap size (`-Xms`).  The heap never shrinks below it.
+//! *   The hard limit (initially `-Xmx`).  The heap never grows beyond it.  It can be changed at run
+//!     time, but never beyond the initial `-Xmx`, for which the address space is reserved.
+//! *   The soft limit (like `SoftMaxHeapSize`, initially the hard limit).  The heap grows beyond it
+//!     only if the live objects do not fit in it with `MinHeapFreeRatio` percent of free space.
+//!
+//! Between the limits, the heap is resized after each GC like HotSpot does with
+//! `MinHeapFreeRatio` and `MaxHeapFreeRatio`.  If the minimum heap size and the hard limit are
+//! equal, the heap size is fixed.
+
+use crate::OpenJDK;
+use mmtk::plan::Plan;
+use mmtk::util::conversions;
+use mmtk::util::heap::GCTriggerPolicy;
+use mmtk::MMTK;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Heap limits in pages.
+#[derive(Clone, Copy)]
+struct Limits {
+    min: usize,
+    soft: usize,
+    hard: usize,
+    /// The initial hard limit.  The address space for the heap is reserved for this size.
+    reserved: usize,
+}
+
+static LIMITS: Mutex<Limits> = Mutex::new(Limits {
+    min: 0,
+    soft: 0,
+    hard: 0,
+    reserved: 0,
+});
+
+/// The current heap size in pages.  A GC is triggered when the reserved pages exceed it.
+static CURRENT_HEAP_PAGES: AtomicUsize = AtomicUsize::new(0);
+/// `MinHeapFreeRatio`, in percent.
+static MIN_HEAP_FREE_RATIO: AtomicUsize = AtomicUsize::new(40);
+/// `MaxHeapFreeRatio`, in percent.
+static MAX_HEAP_FREE_RATIO: AtomicUsize = AtomicUsize::new(70);
+
+/// Use this trigger, so that the limits can be changed at run time.
+static RUNTIME_LIMITS: AtomicBool = AtomicBool::new(false);
+
+/// Return true if the heap limits can be changed at run time.
+pub fn runtime_limits_enabled() -> bool {
+    RUNTIME_LIMITS.load(Ordering::Relaxed)
+}
+
+/// Set the initial heap limits in bytes, and read `MMTK_RUNTIME_HEAP_LIMITS`.  Called before MMTk
+/// is created.
+pub fn set_heap_size(min: usize, max: usize) -> bool {
+    if min > max {
+        return false;
+    }
+    let runtime_limits = matches!(
+        std::env::var("MMTK_RUNTIME_HEAP_LIMITS").as_deref(),
+        Ok("1") | Ok("true")
+    );
+    RUNTIME_LIMITS.store(runtime_limits, Ordering::Relaxed);
+    let min = conversions::bytes_to_pages_up(min);
+    let max = conversions::bytes_to_pages_up(max);
+    *LIMITS.lock().unwrap() = Limits {
+        min,
+        soft: max,
+        hard: max,
+        reserved: max,
+    };
+    CURRENT_HEAP_PAGES.store(min, Ordering::Relaxed);
+    true
+}
+
+/// Set `MinHeapFreeRatio` and `MaxHeapFreeRatio`.
+pub fn set_heap_free_ratios(min: usize, max: usize) {
+    MIN_HEAP_FREE_RATIO.store(min.min(99), Ordering::Relaxed);
+    MAX_HEAP_FREE_RATIO.store(max.clamp(min, 99), Ordering::Relaxed);
+}
+
+/// Change the soft and hard limits (in bytes) at run time.  Return false if the limits are
+/// invalid, or cannot be changed at run time.  The new limits take effect in the next allocation
+/// that may trigger a GC.
+pub fn set_heap_limits(soft: usize, hard: usize) -> bool {
+    if !runtime_limits_enabled() {
+        return false;
+    }
+    let soft = conversions::bytes_to_pages_up(soft);
+    let hard = conversions::bytes_to_pages_up(hard);
+    let mut limits = LIMITS.lock().unwrap();
+    if hard > limits.reserved || hard < limits.min || soft > hard || soft < limits.min {
+        return false;
+    }
+    limits.soft = soft;
+    limits.hard = hard;
+    let current = CURRENT_HEAP_PAGES.load(Ordering::Relaxed);
+    CURRENT_HEAP_PAGES.store(current.clamp(limits.min, soft), Ordering::Relaxed);
+    true
+}
+
+/// The hard limit in bytes.
+pub fn max_heap_bytes() -> usize {
+    conversions::pages_to_bytes(LIMITS.lock().unwrap().hard)
+}
+
+/// The soft limit in bytes.
+pub fn soft_max_heap_bytes() -> usize {
+    conversions::pages_to_bytes(LIMITS.lock().unwrap().soft)
+}
+
+/// The heap size that leaves `free_ratio` percent of it free with `live` pages in use.
+fn heap_size_for_free_ratio(live: usize, free_ratio: usize) -> usize {
+    live * 100 / (100 - free_ratio)
+}
+
+pub struct OpenJDKGCTrigger;
+
+impl<const COMPRESSED: bool> GCTriggerPolicy<OpenJDK<COMPRESSED>> for OpenJDKGCTrigger {
+    fn on_gc_end(&self, mmtk: &'static MMTK<OpenJDK<COMPRESSED>>) {
+        let live = mmtk.get_plan().get_reserved_pages();
+        let limits = *LIMITS.lock().unwrap();
+        let lower = heap_size_for_free_ratio(live, MIN_HEAP_FREE_RATIO.load(Ordering::Relaxed));
+        let upper = heap_size_for_free_ratio(live, MAX_HEAP_FREE_RATIO.load(Ordering::Relaxed));
+        let current = CURRENT_HEAP_PAGES.load(Ordering::Relaxed);
+        // Keep the free space between the ratios, and only exceed the soft limit if the ratios
+        // cannot be kept otherwise.
+        let desired = current.clamp(lower, upper).min(limits.soft.max(lower));
+        CURRENT_HEAP_PAGES.store(desired.clamp(limits.min, limits.hard), Ordering::Relaxed);
+    }
+
+    fn is_heap_full(&self, plan: &dyn Plan<VM = OpenJDK<COMPRESSED>>) -> bool {
+        plan.get_reserved_pages() > CURRENT_HEAP_PAGES.load(Ordering::Relaxed)
+    }
+
+    fn get_current_heap_size_in_pages(&self) -> usize {
+        CURRENT_HEAP_PAGES.load(Ordering::Relaxed)
+    }
+
+    fn get_max_heap_size_in_pages(&self) -> usize {
+        LIMITS.lock().unwrap().hard
+    }
+
+    fn can_heap_size_grow(&self) -> bool {
+        CURRENT_HEAP_PAGES.load(Ordering::Relaxed) < LIMITS.lock().unwrap().hard
+    }
+}
@@ -1,16 +1,19 @@
+use crate::root_stats::{self, RootCategory};
 use crate::scanning;
 use crate::scanning::to_slots_closure;
 use crate::OpenJDK;
 use crate::OpenJDKSlot;
 use crate::UPCALLS;
 use mmtk::scheduler::*;
-use mmtk::util::Address;
+use mmtk::util::{Address, ObjectReference};
+use mmtk::vm::slot::Slot;
 use mmtk::vm::RootsWorkFactory;
 use mmtk::vm::*;
 use mmtk::MMTK;
+use std::collections::HashSet;
 
 macro_rules! scan_roots_work {
-    ($struct_name: ident, $func_name: ident) => {
+    ($struct_name: ident, $func_name: ident, $category: ident) => {
         pub struct $struct_name<VM: VMBinding, F: RootsWorkFactory<VM::VMSlot>> {
             factory: F,
             _p: std::marker::PhantomData<VM>,
@@ -27,28 +30,46 @@ macro_rules! scan_roots_work {
 
         impl<VM: VMBinding, F: RootsWorkFactory<VM::VMSlot>> GCWork<VM> for $struct_name<VM, F> {
             fn do_work(&mut self, _worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
-                unsafe {
+                let _span = crate::trace_recorder::span(stringify!($struct_name), "roots");
+                let _timer = crate::harness::work_timer();
+                let category = RootCategory::$category;
+                probe!(mmtk_openjdk, scan_vm_roots_begin, category as usize);
+                let slots = root_stats::with_category(category, || unsafe {
                     ((*UPCALLS).$func_name)(to_slots_closure(&mut self.factory));
-                }
+                });
+                probe!(mmtk_openjdk, scan_vm_roots_end, category as usize, slots);
             }
         }
     };
 }
 
-scan_roots_work!(ScanUniverseRoots, scan_universe_roots);
-scan_roots_work!(ScanJNIHandlesRoots, scan_jni_handle_roots);
-scan_roots_work!(ScanObjectSynchronizerRoots, scan_object_synchronizer_roots);
-scan_roots_work!(ScanManagementRoots, scan_management_roots);
-scan_roots_work!(ScanJvmtiExportRoots, scan_jvmti_export_roots);
-scan_roots_work!(ScanAOTLoaderRoots, scan_aot_loader_roots);
-scan_roots_work!(ScanSystemDictionaryRoots, scan_system_dictionary_roots);
-scan_roots_work!(ScanStringTableRoots, scan_string_table_roots);
+scan_roots_work!(ScanUniverseRoots, scan_universe_roots, Universe);
+scan_roots_work!(ScanJNIHandlesRoots, scan_jni_handle_roots, JNIHandles);
+scan_roots_work!(
+    ScanObjectSynchronizerRoots,
+    scan_object_synchronizer_roots,
+    ObjectSynchronizer
+);
+scan_roots_work!(ScanManagementRoots, scan_management_roots, Management);
+scan_roots_work!(ScanJvmtiExportRoots, scan_jvmti_export_roots, JvmtiExport);
+scan_roots_work!(ScanAOTLoaderRoots, scan_aot_loader_roots, AOTLoader);
+scan_roots_work!(
+    ScanSystemDictionaryRoots,
+    scan_system_dictionary_roots,
+    SystemDictionary
+);
+scan_roots_work!(ScanStringTableRoots, scan_string_table_roots, StringTable);
 scan_roots_work!(
     ScanClassLoaderDataGraphRoots,
-    scan_class_loader_data_graph_roots
+    scan_class_loader_data_graph_roots,
+    ClassLoaderDataGraph
+);
+scan_roots_work!(
+    ScanWeakProcessorRoots,
+    scan_weak_processor_roots,
+    WeakProcessor
 );
-scan_roots_work!(ScanWeakProcessorRoots, scan_weak_processor_roots);
-scan_roots_work!(ScanVMThreadRoots, scan_vm_thread_roots);
+scan_roots_work!(ScanVMThreadRoots, scan_vm_thread_roots, VMThread);
 
 pub struct ScanCodeCacheRoots<const COMPRESSED: bool, F: RootsWorkFactory<OpenJDKSlot<COMPRESSED>>>
 {
@@ -71,12 +92,16 @@ impl<const COMPRESSED: bool, F: RootsWorkFactory<OpenJDKSlot<COMPRESSED>>>
         _worker: &mut GCWorker<OpenJDK<COMPRESSED>>,
         mmtk: &'static MMTK<OpenJDK<COMPRESSED>>,
     ) {
-        let is_current_gc_nursery = mmtk
-            .get_plan()
-            .generational()
-            .is_some_and(|gen| gen.is_current_gc_nursery());
+        let _span = crate::trace_recorder::span("ScanCodeCacheRoots", "roots");
+        let _timer = crate::harness::work_timer();
+        let is_current_gc_nursery = is_current_gc_nursery(mmtk);
+        let is_code_cache_weak = is_code_cache_weak(mmtk);
+        // If objects may move, remember the old values so that we can find the nmethods that
+        // need fixing up after forwarding.  See `VMCollection::post_forwarding`.
+        let may_move = mmtk.get_plan().current_gc_may_move_object();
 
         let mut slots = Vec::with_capacity(scanning::WORK_PACKET_CAPACITY);
+        let mut snapshots = vec![];
 
         let mut nursery_slots = 0;
         let mut mature_slots = 0;
@@ -94,11 +119,19 @@ impl<const COMPRESSED: bool, F: RootsWorkFactory<OpenJDKSlot<COMPRESSED>>>
         {
             let mut mature = crate::MATURE_CODE_CACHE_ROOTS.lock().unwrap();
 
-            // Only scan mature roots in full-heap collections.
+            // Nursery collections only scan nursery roots.  In full-heap collections of
+            // generational plans, oops in nmethods are weak.  Instead of reporting them as roots, we
+            // process them in `VMScanning::process_weak_refs` and unload nmethods whose oops are
+            // dead.
             if !is_current_gc_nursery {
-                for roots in mature.values() {
+                for (key, roots) in mature.iter() {
                     mature_slots += roots.len();
-                    add_roots(roots);
+                    if !is_code_cache_weak {
+                        add_roots(roots);
+                    }
+                    if may_move {
+                        snapshots.push((*key, load_code_cache_roots::<COMPRESSED>(roots)));
+                    }
                 }
             }
 
@@ -106,13 +139,24 @@ impl<const COMPRESSED: bool, F: RootsWorkFactory<OpenJDKSlot<COMPRESSED>>>
                 let mut nursery = crate::NURSERY_CODE_CACHE_ROOTS.lock().unwrap();
                 for (key, roots) in nursery.drain() {
                     nursery_slots += roots.len();
-                    add_roots(&roots);
+                    if !is_code_cache_weak {
+                        add_roots(&roots);
+                    }
+                    if may_move {
+                        snapshots.push((key, load_code_cache_roots::<COMPRESSED>(&roots)));
+                    }
                     mature.insert(key, roots);
                 }
             }
         }
 
+        // Roots may be scanned more than once in a GC (e.g. MarkCompact).  Only the last scan
+        // happens right before the slots are updated, so it replaces earlier snapshots.
+        *crate::CODE_CACHE_ROOTS_BEFORE_FORWARDING.lock().unwrap() = snapshots;
+
         probe!(mmtk_openjdk, code_cache_roots, nursery_slots, mature_slots);
+        root_stats::record(RootCategory::CodeCacheNursery, nursery_slots);
+        root_stats::record(RootCategory::CodeCacheMature, mature_slots);
 
         if !slots.is_empty() {
             self.factory.create_process_roots_work(slots);
@@ -123,3 +167,147 @@ impl<const COMPRESSED: bool, F: RootsWorkFactory<OpenJDKSlot<COMPRESSED>>>
         // }
     }
 }
+
+/// Scan objects held by the binding for the finalizer thread: objects that are ready for
+/// finalization but not yet taken (`crate::READY_FOR_FINALIZATION`), and, with the
+/// `final_references` feature, objects not yet registered (`crate::FINALIZERS_TO_REGISTER`).
+pub struct ScanFinalizationRoots<
+    const COMPRESSED: bool,
+    F: RootsWorkFactory<OpenJDKSlot<COMPRESSED>>,
+> {
+    factory: F,
+}
+
+impl<const COMPRESSED: bool, F: RootsWorkFactory<OpenJDKSlot<COMPRESSED>>>
+    ScanFinalizationRoots<COMPRESSED, F>
+{
+    pub fn new(factory: F) -> Self {
+        Self { factory }
+    }
+}
+
+impl<const COMPRESSED: bool, F: RootsWorkFactory<OpenJDKSlot<COMPRESSED>>>
+    GCWork<OpenJDK<COMPRESSED>> for ScanFinalizationRoots<COMPRESSED, F>
+{
+    fn do_work(
+        &mut self,
+        _worker: &mut GCWorker<OpenJDK<COMPRESSED>>,
+        _mmtk: &'static MMTK<OpenJDK<COMPRESSED>>,
+    ) {
+        let _span = crate::trace_recorder::span("ScanFinalizationRoots", "roots");
+        let _timer = crate::harness::work_timer();
+        // Mutators are stopped, so the vectors do not change until the GC ends.  The elements
+        // are full-width oops, which untagged slots refer to.
+        let mut scan = |objects: &[ObjectReference]| {
+            for chunk in objects.chunks(scanning::WORK_PACKET_CAPACITY) {
+                let slots = chunk
+                    .iter()
+                    .map(|object| OpenJDKSlot::<COMPRESSED>::from(Address::from_ref(object)))
+                    .collect();
+                self.factory.create_process_roots_work(slots);
+            }
+            root_stats::record(RootCategory::Finalization, objects.len());
+        };
+        scan(&crate::READY_FOR_FINALIZATION.lock().unwrap());
+        #[cfg(feature = "final_references")]
+        scan(&crate::FINALIZERS_TO_REGISTER.lock().unwrap());
+    }
+}
+
+fn load_code_cache_roots<const COMPRESSED: bool>(
+    roots: &[Address],
+) -> Vec<Option<ObjectReference>> {
+    roots
+        .iter()
+        .map(|root| OpenJDKSlot::<COMPRESSED>::from(*root).load())
+        .collect()
+}
+
+pub(crate) fn is_current_gc_nursery<VM: VMBinding>(mmtk: &MMTK<VM>) -> bool {
+    mmtk.get_plan()
+        .generational()
+        .is_some_and(|gen| gen.is_current_gc_nursery())
+}
+
+/// Return true if oops in nmethods are weak in the current GC, so that nmethods with dead oops are
+/// unloaded.  This is only done in full-heap GCs of generational plans.  Non-generational plans
+/// collect the whole heap in every GC, and keep nmethods alive as strong roots.
+pub(crate) fn is_code_cache_weak<VM: VMBinding>(mmtk: &MMTK<VM>) -> bool {
+    mmtk.get_plan()
+        .generational()
+        .is_some_and(|gen| !gen.is_current_gc_nursery())
+}
+
+/// Trace the oops in CodeCache roots, and update the slots if the objects are moved.
+fn forward_code_cache_roots<const COMPRESSED: bool>(
+    roots: &[Address],
+    tracer: &mut impl ObjectTracer,
+) {
+    for root in roots {
+        let slot = OpenJDKSlot::<COMPRESSED>::from(*root);
+        if let Some(object) = slot.load() {
+            let new_object = tracer.trace_object(object);
+            if new_object != object {
+                slot.store(new_object);
+            }
+        }
+    }
+}
+
+/// Keep the oops in the given nmethods alive.  Used for nmethods found on thread stacks, which
+/// must not be unloaded.
+pub(crate) fn keep_nmethods_alive<const COMPRESSED: bool>(
+    nmethods: &HashSet<Address>,
+    tracer: &mut impl ObjectTracer,
+) {
+    let mature = crate::MATURE_CODE_CACHE_ROOTS.lock().unwrap();
+    for nm in nmethods {
+        if let Some(roots) = mature.get(nm) {
+            forward_code_cache_roots::<COMPRESSED>(roots, tracer);
+        }
+    }
+}
+
+/// Process oops in nmethods as weak references.  nmethods with any unreachable oop are removed
+/// from the CodeCache roots and unloaded by the VM.  Oops in other nmethods are updated.
+pub(crate) fn process_weak_code_cache_roots<const COMPRESSED: bool>(
+    tracer: &mut impl ObjectTracer,
+) {
+    let mut dead_nmethods = vec![];
+    {
+        let mut mature = crate::MATURE_CODE_CACHE_ROOTS.lock().unwrap();
+        mature.retain(|nm, roots| {
+            let dead_object = roots.iter().find_map(|root| {
+                OpenJDKSlot::<COMPRESSED>::from(*root)
+                    .load()
+                    .filter(|object| !object.is_reachable())
+            });
+            let Some(dead_object) = dead_object else {
+                forward_code_cache_roots::<COMPRESSED>(roots, tracer);
+                return true;
+            };
+            // Don't leave dangling pointers in the nmethod.  It will never be entered again.
+            for root in roots.iter() {
+                OpenJDKSlot::<COMPRESSED>::from(*root).store_null();
+            }
+            dead_nmethods.push((*nm, dead_object));
+            false
+        });
+    }
+    // The dead object is only reported as the cause of unloading.  It is not moved or kept alive.
+    for (nm, cause) in dead_nmethods {
+        unsafe {
+            ((*UPCALLS).unload_nmethod)(nm, cause);
+        }
+    }
+}
+
+/// Update oops in nmethods that survived `process_weak_code_cache_roots`.
+pub(crate) fn forward_weak_code_cache_roots<const COMPRESSED: bool>(
+    tracer: &mut impl ObjectTracer,
+) {
+    let mature = crate::MATURE_CODE_CACHE_ROOTS.lock().unwrap();
+    for roots in mature.values() {
+        forward_code_cache_roots::<COMPRESSED>(roots, tracer);
+    }
+}
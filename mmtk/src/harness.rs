@@ -0,0 +1,240 @@
+//! Per-iteration statistics between `harness_begin(id)` and `harness_end(id)`.
+//!
+//! Benchmark harnesses such as DaCapo call `harness_begin` and `harness_end` around each iteration
+//! with an iteration id.  mmtk-core only keeps the statistics of the last iteration, so the binding
+//! also keeps statistics of each id:
+//!
+//! *   the wall-clock time of the iteration,
+//! *   the number of GCs and full-heap GCs, and the total and the longest pause,
+//! *   the allocated bytes, estimated from the heap usage before each GC and after the previous one,
+//! *   the time spent in the binding's own work packets (root scanning and weak reference
+//!     processing).  The time of mmtk-core's work packets is in mmtk-core's statistics.
+//!
+//! The statistics of an iteration are printed when it ends.  When the VM exits, a summary of
+//! all iterations is printed, where the last iteration is the timed one and the others are
+//! warm-up, and also written as CSV to the file `MMTK_HARNESS_CSV` if set.
+
+use crate::gc_stats::GCResult;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+#[derive(Clone, Copy, Default)]
+struct IterationStats {
+    id: usize,
+    time_ns: u64,
+    gcs: usize,
+    full_heap_gcs: usize,
+    total_pause_ns: u64,
+    max_pause_ns: u64,
+    allocated_bytes: usize,
+    binding_work_ns: u64,
+}
+
+struct Iteration {
+    stats: IterationStats,
+    start: Instant,
+    /// The used bytes after the last GC, or at the start of the iteration.
+    last_used_bytes: usize,
+}
+
+struct State {
+    current: Option<Iteration>,
+    /// Finished iterations in the order they first ended.  An id that runs again accumulates
+    /// into its entry.
+    finished: Vec<IterationStats>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    current: None,
+    finished: vec![],
+});
+
+/// The id passed to the last `harness_begin`.  The iteration starts after mmtk-core's
+/// `harness_begin`, which does a GC first.
+static PENDING_ID: AtomicUsize = AtomicUsize::new(0);
+/// True during an iteration.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// The time spent in the binding's work packets in the current iteration.
+static BINDING_WORK_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Remember the id of the iteration that is about to begin.
+pub fn set_pending_id(id: usize) {
+    PENDING_ID.store(id, Ordering::SeqCst);
+}
+
+/// Start the iteration of the pending id.  `used_bytes` is the current heap usage.
+pub fn begin(used_bytes: usize) {
+    BINDING_WORK_NS.store(0, Ordering::SeqCst);
+    STATE.lock().unwrap().current = Some(Iteration {
+        stats: IterationStats {
+            id: PENDING_ID.load(Ordering::SeqCst),
+            ..Default::default()
+        },
+        start: Instant::now(),
+        last_used_bytes: used_bytes,
+    });
+    ACTIVE.store(true, Ordering::SeqCst);
+}
+
+/// End the current iteration, and print its statistics.  `id` is the id passed to `harness_end`,
+/// and `used_bytes` is the current heap usage.  The statistics are kept under the id passed to
+/// `harness_begin`, with a warning if the ids differ.
+pub fn end(id: usize, used_bytes: usize) {
+    ACTIVE.store(false, Ordering::SeqCst);
+    let mut state = STATE.lock().unwrap();
+    let Some(iteration) = state.current.take() else {
+        log::warn!("harness_end({}) without harness_begin", id);
+        return;
+    };
+    if iteration.stats.id != id {
+        log::warn!(
+            "harness_end({}) does not match harness_begin({})",
+            id,
+            iteration.stats.id
+        );
+    }
+    let mut stats = iteration.stats;
+    stats.time_ns = iteration.start.elapsed().as_nanos() as u64;
+    stats.allocated_bytes += used_bytes.saturating_sub(iteration.last_used_bytes);
+    stats.binding_work_ns = BINDING_WORK_NS.load(Ordering::SeqCst);
+
+    println!(
+        "============================ MMTk Iteration {} ============================",
+        stats.id
+    );
+    println!("{}", header('\t'));
+    println!("{}", row(&stats, '\t'));
+    println!(
+        "------------------------------ End MMTk Iteration {} ------------------------------",
+        stats.id
+    );
+
+    match state.finished.iter_mut().find(|s| s.id == stats.id) {
+        Some(existing) => {
+            existing.time_ns += stats.time_ns;
+            existing.gcs += stats.gcs;
+            existing.full_heap_gcs += stats.full_heap_gcs;
+            existing.total_pause_ns += stats.total_pause_ns;
+            existing.max_pause_ns = existing.max_pause_ns.max(stats.max_pause_ns);
+            existing.allocated_bytes += stats.allocated_bytes;
+            existing.binding_work_ns += stats.binding_work_ns;
+        }
+        None => state.finished.push(stats),
+    }
+}
+
+/// Count a GC that just finished in the current iteration.
+pub fn record_gc(result: &GCResult) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut state = STATE.lock().unwrap();
+    let Some(iteration) = state.current.as_mut() else {
+        return;
+    };
+    let stats = &mut iteration.stats;
+    stats.gcs += 1;
+    if result.full_heap {
+        stats.full_heap_gcs += 1;
+    }
+    stats.total_pause_ns += result.pause_ns;
+    stats.max_pause_ns = stats.max_pause_ns.max(result.pause_ns);
+    stats.allocated_bytes += result
+        .used_bytes_before
+        .saturating_sub(iteration.last_used_bytes);
+    iteration.last_used_bytes = result.used_bytes_after;
+}
+
+/// Measures the time of a work packet of the binding during an iteration.
+#[must_use]
+pub struct WorkTimer {
+    start: Instant,
+}
+
+impl Drop for WorkTimer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed().as_nanos() as u64;
+        BINDING_WORK_NS.fetch_add(elapsed, Ordering::Relaxed);
+    }
+}
+
+/// Start timing a work packet.  Return `None` if no iteration is running.
+pub fn work_timer() -> Option<WorkTimer> {
+    ACTIVE.load(Ordering::Relaxed).then(|| WorkTimer {
+        start: Instant::now(),
+    })
+}
+
+const COLUMNS: [&str; 8] = [
+    "id",
+    "time.ms",
+    "gc",
+    "gc.full",
+    "pause.total.ms",
+    "pause.max.ms",
+    "allocated.bytes",
+    "binding.work.ms",
+];
+
+fn header(separator: char) -> String {
+    COLUMNS.join(&separator.to_string())
+}
+
+fn row(stats: &IterationStats, separator: char) -> String {
+    let ms = |ns: u64| ns as f64 / 1e6;
+    let values = [
+        stats.id.to_string(),
+        format!("{:.2}", ms(stats.time_ns)),
+        stats.gcs.to_string(),
+        stats.full_heap_gcs.to_string(),
+        format!("{:.2}", ms(stats.total_pause_ns)),
+        format!("{:.2}", ms(stats.max_pause_ns)),
+        stats.allocated_bytes.to_string(),
+        format!("{:.2}", ms(stats.binding_work_ns)),
+    ];
+    values.join(&separator.to_string())
+}
+
+/// The phase of the `index`th of `n` iterations.
+fn phase(index: usize, n: usize) -> &'static str {
+    if index + 1 == n {
+        "timed"
+    } else {
+        "warmup"
+    }
+}
+
+/// Print the summary of all iterations.  Called when the VM exits.
+pub fn print_summary() {
+    let state = STATE.lock().unwrap_or_else(PoisonError::into_inner);
+    let iterations = &state.finished;
+    if iterations.is_empty() {
+        return;
+    }
+    let n = iterations.len();
+
+    println!("============================ MMTk Iteration Summary ============================");
+    println!("phase\t{}", header('\t'));
+    for (index, stats) in iterations.iter().enumerate() {
+        println!("{}\t{}", phase(index, n), row(stats, '\t'));
+    }
+    println!(
+        "------------------------------ End MMTk Iteration Summary ------------------------------"
+    );
+
+    let Ok(path) = std::env::var("MMTK_HARNESS_CSV") else {
+        return;
+    };
+    let mut csv = format!("phase,{}\n", header(','));
+    for (index, stats) in iterations.iter().enumerate() {
+        let _ = writeln!(csv, "{},{}", phase(index, n), row(stats, ','));
+    }
+    if let Err(e) = std::fs::write(&path, csv) {
+        eprintln!(
+            "Failed to write the MMTk iteration statistics to {}: {}",
+            path, e
+        );
+    }
+}
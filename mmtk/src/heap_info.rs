@@ -0,0 +1,155 @@
+//! Printing the heap for crash reports, `jcmd GC.heap_info` and `-Xlog:gc+heap`.
+//!
+//! The text is written into a buffer provided by the caller.  The VM may call this from a signal
+//! handler while reporting a crash, possibly when a GC is in progress or a lock is held by the
+//! crashing thread.  So this must not allocate memory or block on locks.
+
+use crate::OpenJDK;
+use mmtk::policy::space::Space;
+use mmtk::util::conversions;
+use std::fmt::{self, Write};
+use std::sync::atomic::Ordering;
+
+/// Writes into a fixed buffer, and drops what does not fit.
+struct BufferWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for BufferWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// Print the heap into `buf` as a NUL-terminated string, truncated if it does not fit.  Return
+/// the length of the string.
+pub fn print_heap_info(buf: &mut [u8]) -> usize {
+    let Some(capacity) = buf.len().checked_sub(1) else {
+        return 0;
+    };
+    let mut writer = BufferWriter {
+        buf: &mut buf[..capacity],
+        len: 0,
+    };
+    // Do not initialize MMTk here if the VM crashes before MMTk is initialized.
+    let _ = if !crate::MMTK_INITIALIZED.load(Ordering::SeqCst) {
+        writeln!(writer, " MMTk heap is not initialized")
+    } else if crate::use_compressed_oops() {
+        print_heap::<true>(&mut writer)
+    } else {
+        print_heap::<false>(&mut writer)
+    };
+    let len = writer.len;
+    buf[len] = 0;
+    len
+}
+
+fn print_heap<const COMPRESSED: bool>(w: &mut impl Write) -> fmt::Result {
+    let mmtk = crate::singleton::<COMPRESSED>();
+    let plan = mmtk.get_plan();
+    let options = mmtk.get_options();
+    let k = |pages: usize| conversions::pages_to_bytes(pages) >> 10;
+    writeln!(
+        w,
+        " MMTk {:?} heap      total {}K, used {}K, reserved {}K [{}, {})",
+        *options.plan,
+        k(plan.get_total_pages()),
+        k(plan.get_used_pages()),
+        k(plan.get_reserved_pages()),
+        mmtk::memory_manager::starting_heap_address(),
+        mmtk::memory_manager::last_heap_address(),
+    )?;
+    writeln!(
+        w,
+        "  GC {}, {} worker threads",
+        if mmtk.gc_in_progress() {
+            "in progress"
+        } else {
+            "idle"
+        },
+        *options.threads,
+    )?;
+
+    let mut result = Ok(());
+    plan.for_each_space(&mut |space| {
+        if result.is_ok() {
+            result = print_space(w, space);
+        }
+    });
+    result?;
+
+    if crate::use_compressed_oops() {
+        let base = crate::slots::BASE.load(Ordering::Relaxed);
+        let shift = crate::slots::SHIFT.load(Ordering::Relaxed);
+        let mode = match (base.is_zero(), shift) {
+            (true, 0) => "32-bit",
+            (true, _) => "Zero based",
+            (false, _) => "Non-zero based",
+        };
+        let (klass_base, klass_shift) = crate::abi::compressed_klass_base_and_shift();
+        writeln!(
+            w,
+            "  Compressed oops: {}, base {}, shift {}; compressed class base {}, shift {}",
+            mode, base, shift, klass_base, klass_shift,
+        )?;
+    } else {
+        writeln!(w, "  Compressed oops: disabled")?;
+    }
+
+    if let Some(gc) = crate::gc_stats::try_last_gc_result() {
+        if gc.index != 0 {
+            writeln!(
+                w,
+                "  Last GC({}) {}: {}K->{}K({}K), pause {}us",
+                gc.index,
+                if gc.full_heap { "full heap" } else { "nursery" },
+                gc.used_bytes_before >> 10,
+                gc.used_bytes_after >> 10,
+                gc.total_bytes >> 10,
+                gc.pause_ns / 1000,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Print the pages of a space.  Used pages are counted as in the heap usage above, i.e. the pages
+/// reserved by the space and its side metadata.  Reserved pages are the pages the space has
+/// claimed, and committed pages are those actually mapped for it.  MMTk does not count the bytes
+/// of objects per space.
+fn print_space<const COMPRESSED: bool>(
+    w: &mut impl Write,
+    space: &dyn Space<OpenJDK<COMPRESSED>>,
+) -> fmt::Result {
+    let common = space.common();
+    let pr = space.get_page_resource();
+    let used = conversions::pages_to_bytes(space.reserved_pages()) >> 10;
+    let reserved = conversions::pages_to_bytes(pr.reserved_pages()) >> 10;
+    let committed = conversions::pages_to_bytes(pr.committed_pages()) >> 10;
+    if common.contiguous {
+        writeln!(
+            w,
+            "  {:<16} used {}K, reserved {}K, committed {}K, address range {}K [{}, {})",
+            space.get_name(),
+            used,
+            reserved,
+            committed,
+            common.extent >> 10,
+            common.start,
+            common.start + common.extent,
+        )
+    } else {
+        writeln!(
+            w,
+            "  {:<16} used {}K, reserved {}K, committed {}K, discontiguous",
+            space.get_name(),
+            used,
+            reserved,
+            committed,
+        )
+    }
+}
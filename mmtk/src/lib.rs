@@ -3,7 +3,7 @@ extern crate lazy_static;
 #[macro_use]
 extern crate probe;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ptr::null_mut;
 use std::sync::Mutex;
 
@@ -24,12 +24,25 @@ pub mod active_plan;
 pub mod api;
 mod build_info;
 pub mod collection;
+mod gc_event_log;
+mod gc_stats;
+mod gc_trigger;
 mod gc_work;
+mod harness;
+mod heap_info;
+mod logging;
 pub mod object_model;
 mod object_scanning;
+mod periodic_gc;
 pub mod reference_glue;
+#[cfg(feature = "final_references")]
+mod reference_processor;
+mod reference_stats;
+mod root_stats;
 pub mod scanning;
 mod slots;
+mod space_usage;
+mod trace_recorder;
 pub(crate) mod vm_metadata;
 
 #[repr(C)]
@@ -68,6 +81,19 @@ impl MutatorClosure {
     }
 }
 
+/// How the objects referred by a buffer of root slots are treated.  Must match `RootsKind` in
+/// `mmtk.h`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RootsKind {
+    /// The objects may be moved, and the slots will be updated.
+    Normal = 0,
+    /// The objects will not be moved in this GC, but their children may be.
+    Pinning = 1,
+    /// Neither the objects nor any object reachable from them will be moved in this GC.
+    TransitivelyPinning = 2,
+}
+
 /// A closure for reporting root slots.  The C++ code should pass `data` back as the last argument.
 #[repr(C)]
 pub struct SlotsClosure {
@@ -75,6 +101,7 @@ pub struct SlotsClosure {
         buf: *mut Address,
         size: usize,
         cap: usize,
+        kind: RootsKind,
         data: *mut libc::c_void,
     ) -> NewBuffer,
     pub data: *const libc::c_void,
@@ -86,7 +113,11 @@ pub struct OpenJDK_Upcalls {
     pub resume_mutators: extern "C" fn(tls: VMWorkerThread),
     pub spawn_gc_thread: extern "C" fn(tls: VMThread, kind: libc::c_int, ctx: *mut libc::c_void),
     pub block_for_gc: extern "C" fn(),
-    pub out_of_memory: extern "C" fn(tls: VMThread, err_kind: AllocationError),
+    pub out_of_memory: extern "C" fn(
+        tls: VMThread,
+        err_kind: AllocationError,
+        report: *const collection::OutOfMemoryReport,
+    ),
     pub get_mutators: extern "C" fn(closure: MutatorClosure),
     pub scan_object: extern "C" fn(trace: *mut c_void, object: ObjectReference, tls: OpaquePointer),
     pub dump_object: extern "C" fn(object: ObjectReference),
@@ -94,7 +125,7 @@ pub struct OpenJDK_Upcalls {
     pub get_mmtk_mutator: extern "C" fn(tls: VMMutatorThread) -> *mut libc::c_void,
     pub is_mutator: extern "C" fn(tls: VMThread) -> bool,
     pub harness_begin: extern "C" fn(),
-    pub harness_end: extern "C" fn(),
+    pub harness_end: extern "C" fn(id: usize),
     pub compute_klass_mem_layout_checksum: extern "C" fn() -> usize,
     pub offset_of_static_fields: extern "C" fn() -> i32,
     pub static_oop_field_count_offset: extern "C" fn() -> i32,
@@ -119,6 +150,18 @@ pub struct OpenJDK_Upcalls {
     pub schedule_finalizer: extern "C" fn(),
     pub prepare_for_roots_re_scanning: extern "C" fn(),
     pub enqueue_references: extern "C" fn(objects: *const ObjectReference, len: usize),
+    pub fix_nmethod_relocations: extern "C" fn(nm: Address),
+    pub unload_nmethod: extern "C" fn(nm: Address, cause: ObjectReference),
+    pub get_conservative_stack_range:
+        extern "C" fn(tls: VMMutatorThread, start: *mut Address, end: *mut Address),
+    pub soft_ref_timestamp_offset: extern "C" fn() -> i32,
+    pub soft_ref_clock: extern "C" fn() -> i64,
+    pub soft_ref_lru_policy_ms_per_mb: extern "C" fn() -> i64,
+    pub should_clear_all_soft_refs: extern "C" fn() -> bool,
+    pub notify_gc_start: extern "C" fn(notification: *const gc_stats::GCNotification),
+    pub notify_gc_end: extern "C" fn(notification: *const gc_stats::GCNotification),
+    pub log_is_enabled: extern "C" fn(level: i32, tags: logging::LogTags) -> bool,
+    pub log_message: extern "C" fn(level: i32, tags: logging::LogTags, message: *const c_char),
 }
 
 pub static mut UPCALLS: *const OpenJDK_Upcalls = null_mut();
@@ -157,11 +200,25 @@ impl<const COMPRESSED: bool> VMBinding for OpenJDK<COMPRESSED> {
     const USE_ALLOCATION_OFFSET: bool = false;
 }
 
-use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 
 pub static MMTK_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
+/// The number of objects registered for finalization.
+static FINALIZABLE_OBJECTS_REGISTERED: AtomicUsize = AtomicUsize::new(0);
+/// The number of objects handed to the VM to be finalized.
+static FINALIZABLE_OBJECTS_FINALIZED: AtomicUsize = AtomicUsize::new(0);
+/// Objects ready for finalization, taken from mmtk-core at the end of each GC so that the
+/// finalizer thread can take them in batches.  MMTk no longer knows them, so they are roots until
+/// the finalizer thread takes them.  See `ScanFinalizationRoots`.
+static READY_FOR_FINALIZATION: Mutex<Vec<ObjectReference>> = Mutex::new(vec![]);
+
+/// Finalizable objects waiting for the finalizer thread to register them with
+/// `Finalizer.register()`.  They are roots until then.  See `api::mmtk_defer_finalizer_registration`.
+#[cfg(feature = "final_references")]
+static FINALIZERS_TO_REGISTER: Mutex<Vec<ObjectReference>> = Mutex::new(vec![]);
+
 lazy_static! {
     pub static ref BUILDER: Mutex<MMTKBuilder> = Mutex::new(MMTKBuilder::new_no_env_vars());
     pub static ref SINGLETON_COMPRESSED: MMTK<OpenJDK<true>> = {
@@ -207,10 +264,34 @@ lazy_static! {
     static ref NURSERY_CODE_CACHE_ROOTS: Mutex<HashMap<Address, Vec<Address>>> = Mutex::new(HashMap::new());
     /// A global storage for all the cached CodeCache roots added before the last GC.
     static ref MATURE_CODE_CACHE_ROOTS: Mutex<HashMap<Address, Vec<Address>>> = Mutex::new(HashMap::new());
+    /// The values of the CodeCache roots scanned in the current GC, recorded before they are
+    /// forwarded.  Only populated if the current GC may move objects.
+    static ref CODE_CACHE_ROOTS_BEFORE_FORWARDING: Mutex<Vec<(Address, Vec<Option<ObjectReference>>)>> = Mutex::new(vec![]);
+    /// nmethods found on thread stacks in the current GC.  They are never unloaded.
+    static ref ON_STACK_NMETHODS: Mutex<HashSet<Address>> = Mutex::new(HashSet::new());
+}
+
+/// The number of shards of `PIN_COUNTS`.
+#[cfg(feature = "object_pinning")]
+const PIN_COUNT_SHARDS: usize = 32;
+
+#[cfg(feature = "object_pinning")]
+lazy_static! {
+    /// The number of outstanding pins of each pinned object.  An object may be pinned by several
+    /// threads (e.g. nested JNI critical regions), and is only unpinned when all of them are done.
+    /// The counts are sharded by address so that threads pinning different objects rarely contend.
+    static ref PIN_COUNTS: [Mutex<HashMap<ObjectReference, usize>>; PIN_COUNT_SHARDS] = Default::default();
+}
+
+/// The shard of `PIN_COUNTS` for `object`.
+#[cfg(feature = "object_pinning")]
+fn pin_counts(object: ObjectReference) -> &'static Mutex<HashMap<ObjectReference, usize>> {
+    // Objects are at least 8-byte aligned.
+    &PIN_COUNTS[(object.to_raw_address().as_usize() >> 3) % PIN_COUNT_SHARDS]
 }
 
 fn set_compressed_pointer_vm_layout(builder: &mut MMTKBuilder) {
-    let max_heap_size = builder.options.gc_trigger.max_heap_size();
+    let max_heap_size = gc_trigger::max_heap_bytes();
     assert!(
         max_heap_size <= (32usize << LOG_BYTES_IN_GBYTE),
         "Heap size is larger than 32 GB"
@@ -0,0 +1,93 @@
+//! Forward log records of mmtk-core and the binding to HotSpot unified logging.
+//!
+//! Each record is logged with a HotSpot tag set chosen by the module it comes from, so that
+//! `-Xlog:gc*`, `-Xlog:gc+heap=debug` and so on control MMTk output like the output of HotSpot's
+//! own GCs, with the decorations and outputs configured by `-Xlog`.
+//!
+//! If `RUST_LOG` is set, the logger is not installed, and mmtk-core logs to stderr with its
+//! built-in `env_logger` as before.
+
+use crate::UPCALLS;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::ffi::CString;
+
+/// HotSpot tag sets for MMTk log records.  Must match `MMTkLogTags` in `mmtk.h`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogTags {
+    /// `gc`
+    Gc = 0,
+    /// `gc+heap`: heap sizes, spaces and allocation.
+    GcHeap = 1,
+    /// `gc+ref`: reference processing and finalization.
+    GcRef = 2,
+    /// `gc+phases`: the scheduler and work packets.
+    GcPhases = 3,
+    /// `gc+init`: options and the creation of MMTk.
+    GcInit = 4,
+}
+
+/// Module path prefixes and their tag sets.  The first match is used.
+const TAGS_BY_TARGET: &[(&str, LogTags)] = &[
+    ("mmtk::util::reference_processor", LogTags::GcRef),
+    ("mmtk::util::finalizable_processor", LogTags::GcRef),
+    ("mmtk_openjdk::reference", LogTags::GcRef),
+    ("mmtk::util::heap", LogTags::GcHeap),
+    ("mmtk::util::alloc", LogTags::GcHeap),
+    ("mmtk::policy", LogTags::GcHeap),
+    ("mmtk_openjdk::gc_trigger", LogTags::GcHeap),
+    ("mmtk::scheduler", LogTags::GcPhases),
+    ("mmtk::util::options", LogTags::GcInit),
+    ("mmtk::memory_manager", LogTags::GcInit),
+    ("mmtk::mmtk", LogTags::GcInit),
+];
+
+fn tags_for_target(target: &str) -> LogTags {
+    TAGS_BY_TARGET
+        .iter()
+        .find(|(prefix, _)| target.starts_with(prefix))
+        .map_or(LogTags::Gc, |(_, tags)| *tags)
+}
+
+/// The HotSpot log level of a record: 1 (error) to 5 (trace), as `log::Level`.
+fn level_value(level: Level) -> i32 {
+    level as i32
+}
+
+struct HotSpotLogger;
+
+impl Log for HotSpotLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let tags = tags_for_target(metadata.target());
+        unsafe { ((*UPCALLS).log_is_enabled)(level_value(metadata.level()), tags) }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        // Interior NULs would truncate the message in HotSpot anyway.
+        let message = CString::new(message.replace('\0', " ")).unwrap();
+        let tags = tags_for_target(record.target());
+        unsafe {
+            ((*UPCALLS).log_message)(level_value(record.level()), tags, message.as_ptr());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: HotSpotLogger = HotSpotLogger;
+
+/// Install the logger unless `RUST_LOG` is set.  Must be called after the upcalls are set, and
+/// before MMTk is created, which would install mmtk-core's `env_logger` otherwise.
+pub fn init() {
+    if std::env::var_os("RUST_LOG").is_some() {
+        return;
+    }
+    if log::set_logger(&LOGGER).is_ok() {
+        // HotSpot decides which records are logged.  The configuration may change at run time.
+        log::set_max_level(LevelFilter::Trace);
+    }
+}
@@ -125,15 +125,39 @@ impl OopIterate for InstanceRefKlass {
 
         if Self::should_scan_weak_refs::<COMPRESSED>() {
             let reference = ObjectReference::from(oop);
-            match self.instance_klass.reference_type {
+            let reference_type = self.instance_klass.reference_type;
+            #[cfg(feature = "final_references")]
+            {
+                use crate::reference_processor::{discover_reference, Discovery};
+                match discover_reference(reference, reference_type) {
+                    Discovery::Discovered => return,
+                    Discovery::Strong => return Self::process_ref_as_strong(oop, closure),
+                    Discovery::NotHandled => {}
+                }
+            }
+            if matches!(
+                reference_type,
+                ReferenceType::Weak | ReferenceType::Soft | ReferenceType::Phantom
+            ) {
+                crate::reference_stats::record_discovered(reference_type);
+            }
+            match reference_type {
                 ReferenceType::None => {
                     panic!("oop_iterate on InstanceRefKlass with reference_type as None")
                 }
                 ReferenceType::Weak => add_weak_candidate(reference),
-                ReferenceType::Soft => add_soft_candidate(reference),
+                ReferenceType::Soft => {
+                    // Soft references that should be cleared are processed like weak references.
+                    if crate::reference_glue::should_clear_soft_reference(oop) {
+                        add_weak_candidate(reference)
+                    } else {
+                        add_soft_candidate(reference)
+                    }
+                }
                 ReferenceType::Phantom => add_phantom_candidate(reference),
                 // Process these two types normally (as if they are strong refs)
-                // We will handle final reference later
+                // Final references are handled by `reference_processor` if the `final_references`
+                // feature is enabled.  Otherwise, finalization is done by mmtk-core.
                 ReferenceType::Final | ReferenceType::Other => {
                     Self::process_ref_as_strong(oop, closure)
                 }
@@ -0,0 +1,82 @@
+//! Periodic GC for idle VMs, like `G1PeriodicGCInterval` and `G1PeriodicGCSystemLoadThreshold`.
+//!
+//! GCs are normally triggered by allocation, so a VM that becomes idle keeps the heap it had at
+//! its peak.  If enabled, the binding's periodic GC thread (`MMTkPeriodicGCThread`) wakes up every
+//! interval, and triggers a full-heap GC if no GC has happened in the interval, and the system is
+//! not busy.
+//!
+//! It is configured by environment variables:
+//!
+//! *   `MMTK_PERIODIC_GC_INTERVAL`: The interval in milliseconds.  0 (the default) disables periodic
+//!     GC.
+//! *   `MMTK_PERIODIC_GC_SYSTEM_LOAD_THRESHOLD`: Do not trigger periodic GCs if the one-minute
+//!     system load average is above this.  0 (the default) means no limit.
+//!
+//! After a periodic GC, the thread returns the memory freed by `malloc` to the OS with
+//! `malloc_trim`.  This covers the spaces of the malloc mark-sweep plan, and the memory of the VM
+//! and the binding.  Releasing the free pages of other MMTk spaces to the OS is not supported:
+//! mmtk-core keeps those pages mapped after freeing them, and has no API for a binding to unmap
+//! them.  For those spaces, a periodic GC only lowers the heap size chosen by the dynamic heap
+//! trigger.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+static INTERVAL_MS: AtomicU64 = AtomicU64::new(0);
+/// The bits of a `f64`.
+static SYSTEM_LOAD_THRESHOLD: AtomicU64 = AtomicU64::new(0);
+
+/// Read and parse an environment variable.  A malformed value is ignored with a warning.
+fn read_env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            log::warn!("Ignoring invalid value of {}: {}", name, value);
+            None
+        }
+    }
+}
+
+/// Read the settings from environment variables.
+pub fn init() {
+    if let Some(interval) = read_env_var::<u64>("MMTK_PERIODIC_GC_INTERVAL") {
+        INTERVAL_MS.store(interval, Ordering::Relaxed);
+    }
+    if let Some(threshold) = read_env_var::<f64>("MMTK_PERIODIC_GC_SYSTEM_LOAD_THRESHOLD") {
+        SYSTEM_LOAD_THRESHOLD.store(threshold.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// The periodic GC interval in milliseconds, or 0 if periodic GC is disabled.
+pub fn interval_ms() -> u64 {
+    INTERVAL_MS.load(Ordering::Relaxed)
+}
+
+fn system_load_is_low() -> bool {
+    let threshold = f64::from_bits(SYSTEM_LOAD_THRESHOLD.load(Ordering::Relaxed));
+    if threshold <= 0.0 {
+        return true;
+    }
+    let mut load = 0.0;
+    // If the load average is not available, assume the system is idle.
+    let available = unsafe { libc::getloadavg(&mut load, 1) } == 1;
+    !available || load <= threshold
+}
+
+/// Return true if the periodic GC thread should trigger a GC now.
+pub fn should_trigger() -> bool {
+    let interval = interval_ms();
+    interval != 0
+        && crate::gc_stats::time_since_last_gc() >= Duration::from_millis(interval)
+        && system_load_is_low()
+}
+
+/// Return free memory to the OS after a periodic GC.  Only the memory freed by `malloc` is
+/// returned.  Releasing the free pages of other MMTk spaces is not supported.
+pub fn release_free_memory() {
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    unsafe {
+        libc::malloc_trim(0);
+    }
+}
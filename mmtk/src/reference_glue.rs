@@ -1,10 +1,13 @@
 use crate::abi::{InstanceRefKlass, Oop};
 use crate::OpenJDK;
 use crate::UPCALLS;
+use mmtk::memory_manager;
+use mmtk::util::constants::BYTES_IN_MBYTE;
 use mmtk::util::opaque_pointer::VMWorkerThread;
 use mmtk::util::ObjectReference;
 use mmtk::vm::slot::Slot;
 use mmtk::vm::ReferenceGlue;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
 
 pub struct VMReferenceGlue {}
 
@@ -12,6 +15,9 @@ impl<const COMPRESSED: bool> ReferenceGlue<OpenJDK<COMPRESSED>> for VMReferenceG
     type FinalizableType = ObjectReference;
 
     fn set_referent(reff: ObjectReference, referent: ObjectReference) {
+        // mmtk-core only sets the referent of a reference it keeps, to the (forwarded) live
+        // referent.
+        crate::reference_stats::record_retained::<COMPRESSED>(reff);
         let oop = Oop::from(reff);
         InstanceRefKlass::referent_address::<COMPRESSED>(oop).store(referent);
     }
@@ -20,12 +26,58 @@ impl<const COMPRESSED: bool> ReferenceGlue<OpenJDK<COMPRESSED>> for VMReferenceG
         InstanceRefKlass::referent_address::<COMPRESSED>(oop).load()
     }
     fn enqueue_references(references: &[ObjectReference], _tls: VMWorkerThread) {
+        crate::reference_stats::record_enqueued::<COMPRESSED>(references);
+        probe!(mmtk_openjdk, enqueue_references, references.len());
         unsafe {
             ((*UPCALLS).enqueue_references)(references.as_ptr(), references.len());
         }
     }
     fn clear_referent(new_reference: ObjectReference) {
+        crate::reference_stats::record_cleared::<COMPRESSED>(new_reference);
         let oop = Oop::from(new_reference);
         InstanceRefKlass::referent_address::<COMPRESSED>(oop).store_null();
     }
 }
+
+/// The heap usage at the end of the last GC.
+static USED_BYTES_AT_LAST_GC: AtomicUsize = AtomicUsize::new(0);
+/// The value of `SoftReference.clock` at the start of the current GC.
+static SOFT_REF_CLOCK: AtomicI64 = AtomicI64::new(0);
+/// Soft references not accessed for longer than this (in milliseconds) are cleared.
+static SOFT_REF_MAX_INTERVAL: AtomicI64 = AtomicI64::new(0);
+/// Clear all soft references in the current GC.
+static CLEAR_ALL_SOFT_REFS: AtomicBool = AtomicBool::new(false);
+
+/// Set up the soft reference clearing policy for the current GC.  Like HotSpot's
+/// `LRUMaxHeapPolicy`, a soft reference is kept for `SoftRefLRUPolicyMSPerMB` milliseconds since
+/// its last access for every megabyte of free heap at the end of the last GC.  Like HotSpot's
+/// last-ditch collection, an emergency collection clears all soft references.
+pub fn setup_soft_ref_policy<const COMPRESSED: bool>() {
+    let max_heap_size = crate::gc_trigger::max_heap_bytes();
+    let free_mb = max_heap_size.saturating_sub(USED_BYTES_AT_LAST_GC.load(Ordering::Relaxed))
+        / BYTES_IN_MBYTE;
+    let ms_per_mb = unsafe { ((*UPCALLS).soft_ref_lru_policy_ms_per_mb)() };
+    let clear_all = unsafe { ((*UPCALLS).should_clear_all_soft_refs)() }
+        || crate::gc_stats::is_near_gc_overhead_limit()
+        || crate::singleton::<COMPRESSED>().is_emergency_collection();
+    SOFT_REF_CLOCK.store(unsafe { ((*UPCALLS).soft_ref_clock)() }, Ordering::Relaxed);
+    SOFT_REF_MAX_INTERVAL.store(free_mb as i64 * ms_per_mb, Ordering::Relaxed);
+    CLEAR_ALL_SOFT_REFS.store(clear_all, Ordering::Relaxed);
+}
+
+/// Record the heap usage after a GC for the soft reference clearing policy of the next GC.
+pub fn record_used_bytes_after_gc<const COMPRESSED: bool>() {
+    let used_bytes = memory_manager::used_bytes(crate::singleton::<COMPRESSED>());
+    USED_BYTES_AT_LAST_GC.store(used_bytes, Ordering::Relaxed);
+}
+
+/// Return true if the referent of the soft reference should be cleared if it is not strongly
+/// reachable.  Otherwise, the referent is kept alive in this GC.
+pub fn should_clear_soft_reference(reference: Oop) -> bool {
+    if CLEAR_ALL_SOFT_REFS.load(Ordering::Relaxed) {
+        return true;
+    }
+    let interval =
+        SOFT_REF_CLOCK.load(Ordering::Relaxed) - InstanceRefKlass::soft_ref_timestamp(reference);
+    interval > SOFT_REF_MAX_INTERVAL.load(Ordering::Relaxed)
+}
@@ -0,0 +1,165 @@
+//! Processing of `FinalReference` and `PhantomReference` in the binding.
+//!
+//! mmtk-core processes soft, weak and phantom references before it calls
+//! `Scanning::process_weak_refs`.  But the Java spec requires that objects are finalized before
+//! they become phantom reachable.  So we discover final and phantom references ourselves, and
+//! process them in `process_weak_refs`:
+//!
+//! 1.  For each discovered `FinalReference` whose referent is unreachable, resurrect the referent
+//!     and enqueue the reference so that `java.lang.ref.Finalizer` will finalize the referent.
+//!     References discovered while tracing from resurrected objects are processed in the same
+//!     way, until no more `FinalReference` is discovered.
+//! 2.  Then clear and enqueue each discovered `PhantomReference` whose referent is unreachable.
+//!
+//! Soft and weak references are still processed by mmtk-core, before finalization.
+
+use crate::abi::{InstanceRefKlass, Oop, ReferenceType};
+use crate::{OpenJDK, UPCALLS};
+use mmtk::scheduler::GCWorker;
+use mmtk::util::ObjectReference;
+use mmtk::vm::slot::Slot;
+use mmtk::vm::{ObjectTracer, ObjectTracerContext};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+/// Not in a GC, or the references have been processed.
+const DISCOVERY_CLOSED: u8 = 0;
+/// Tracing from roots.  mmtk-core discovers soft and weak references.
+const DISCOVERING: u8 = 1;
+/// Processing final and phantom references.  mmtk-core has processed soft and weak references.
+const PROCESSING: u8 = 2;
+
+static PHASE: AtomicU8 = AtomicU8::new(DISCOVERY_CLOSED);
+
+static DISCOVERED_FINAL_REFS: Mutex<Vec<ObjectReference>> = Mutex::new(vec![]);
+static DISCOVERED_PHANTOM_REFS: Mutex<Vec<ObjectReference>> = Mutex::new(vec![]);
+
+/// How a reference object found when scanning objects should be treated.
+pub enum Discovery {
+    /// The reference is discovered by the binding.  Its referent should not be traced.
+    Discovered,
+    /// Treat the reference as a strong reference.
+    Strong,
+    /// Let mmtk-core discover the reference.
+    NotHandled,
+}
+
+/// Start discovering references for a new GC.
+pub fn enable_discovery() {
+    PHASE.store(DISCOVERING, Ordering::SeqCst);
+}
+
+/// Decide how to treat a reference found when scanning objects.
+pub fn discover_reference(reference: ObjectReference, ty: ReferenceType) -> Discovery {
+    match (ty, PHASE.load(Ordering::Relaxed)) {
+        // e.g. the second trace of MarkCompact.  The references are already processed, and we only
+        // need to update the referents.
+        (ReferenceType::Final | ReferenceType::Phantom, DISCOVERY_CLOSED) => Discovery::Strong,
+        (ReferenceType::Final, _) => {
+            crate::reference_stats::record_discovered(ty);
+            DISCOVERED_FINAL_REFS.lock().unwrap().push(reference);
+            Discovery::Discovered
+        }
+        (ReferenceType::Phantom, _) => {
+            crate::reference_stats::record_discovered(ty);
+            DISCOVERED_PHANTOM_REFS.lock().unwrap().push(reference);
+            Discovery::Discovered
+        }
+        // Found when tracing from resurrected objects.  It is too late for mmtk-core to process
+        // them, so we keep their referents alive.
+        (ReferenceType::Soft | ReferenceType::Weak, PROCESSING) => Discovery::Strong,
+        _ => Discovery::NotHandled,
+    }
+}
+
+/// Process discovered final and phantom references.  Return true if this needs to be called
+/// again after the transitive closure from resurrected objects is computed.
+pub fn process_references<const COMPRESSED: bool>(
+    worker: &mut GCWorker<OpenJDK<COMPRESSED>>,
+    tracer_context: &impl ObjectTracerContext<OpenJDK<COMPRESSED>>,
+) -> bool {
+    if PHASE.load(Ordering::SeqCst) == DISCOVERY_CLOSED {
+        return false;
+    }
+    PHASE.store(PROCESSING, Ordering::SeqCst);
+
+    let final_refs = std::mem::take(&mut *DISCOVERED_FINAL_REFS.lock().unwrap());
+    if !final_refs.is_empty() {
+        tracer_context.with_tracer(worker, |tracer| {
+            process_final_refs::<COMPRESSED>(&final_refs, tracer)
+        });
+        // Resurrected objects may lead to more final references.
+        return true;
+    }
+
+    // All objects that will be finalized are resurrected.  Now we know which objects are phantom
+    // reachable.
+    let phantom_refs = std::mem::take(&mut *DISCOVERED_PHANTOM_REFS.lock().unwrap());
+    PHASE.store(DISCOVERY_CLOSED, Ordering::SeqCst);
+    tracer_context.with_tracer(worker, |tracer| {
+        process_phantom_refs::<COMPRESSED>(&phantom_refs, tracer)
+    });
+    false
+}
+
+fn process_final_refs<const COMPRESSED: bool>(
+    references: &[ObjectReference],
+    tracer: &mut impl ObjectTracer,
+) {
+    let mut to_enqueue = vec![];
+    for reference in references {
+        let slot = InstanceRefKlass::referent_address::<COMPRESSED>(Oop::from(*reference));
+        let Some(referent) = slot.load() else {
+            continue;
+        };
+        if referent.is_reachable() {
+            crate::reference_stats::record_retained::<COMPRESSED>(*reference);
+        } else {
+            // Unlike other references, the referent is not cleared.  The finalizer needs it.
+            to_enqueue.push(*reference);
+        }
+        // This resurrects the referent if it is unreachable.
+        let new_referent = tracer.trace_object(referent);
+        if new_referent != referent {
+            slot.store(new_referent);
+        }
+    }
+    crate::FINALIZABLE_OBJECTS_FINALIZED.fetch_add(to_enqueue.len(), Ordering::Relaxed);
+    enqueue_references::<COMPRESSED>(&to_enqueue);
+}
+
+fn process_phantom_refs<const COMPRESSED: bool>(
+    references: &[ObjectReference],
+    tracer: &mut impl ObjectTracer,
+) {
+    let mut to_enqueue = vec![];
+    for reference in references {
+        let slot = InstanceRefKlass::referent_address::<COMPRESSED>(Oop::from(*reference));
+        let Some(referent) = slot.load() else {
+            continue;
+        };
+        if referent.is_reachable() {
+            crate::reference_stats::record_retained::<COMPRESSED>(*reference);
+            let new_referent = tracer.trace_object(referent);
+            if new_referent != referent {
+                slot.store(new_referent);
+            }
+        } else {
+            slot.store_null();
+            crate::reference_stats::record_cleared::<COMPRESSED>(*reference);
+            to_enqueue.push(*reference);
+        }
+    }
+    enqueue_references::<COMPRESSED>(&to_enqueue);
+}
+
+fn enqueue_references<const COMPRESSED: bool>(references: &[ObjectReference]) {
+    if references.is_empty() {
+        return;
+    }
+    crate::reference_stats::record_enqueued::<COMPRESSED>(references);
+    probe!(mmtk_openjdk, enqueue_references, references.len());
+    unsafe {
+        ((*UPCALLS).enqueue_references)(references.as_ptr(), references.len());
+    }
+}
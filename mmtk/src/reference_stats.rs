@@ -0,0 +1,142 @@
+//! Per-GC statistics of reference processing.
+
+use crate::abi::{InstanceKlass, Oop, ReferenceType};
+use mmtk::util::ObjectReference;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Counts of references of one type in a GC.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReferenceStats {
+    /// References found when scanning objects, and handed to reference processing.  In
+    /// MarkCompact, references found in both traces are counted twice.
+    pub discovered: usize,
+    /// References whose referents were cleared.
+    pub cleared: usize,
+    /// References whose referents were kept alive, either because they were reachable, or
+    /// because they were soft references kept by the LRU policy.  In MarkCompact, soft and weak
+    /// references are counted again when their referents are updated in the second trace.
+    pub retained: usize,
+    /// References added to the pending list.
+    pub enqueued: usize,
+}
+
+/// Counts of references of all types in a GC.  Must match `ReferenceProcessingStats` in
+/// `mmtk.h`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReferenceProcessingStats {
+    pub soft: ReferenceStats,
+    pub weak: ReferenceStats,
+    pub final_: ReferenceStats,
+    pub phantom: ReferenceStats,
+}
+
+const NUM_KINDS: usize = 4;
+
+#[derive(Default)]
+struct Counters {
+    discovered: [AtomicUsize; NUM_KINDS],
+    cleared: [AtomicUsize; NUM_KINDS],
+    retained: [AtomicUsize; NUM_KINDS],
+    enqueued: [AtomicUsize; NUM_KINDS],
+}
+
+lazy_static! {
+    static ref CURRENT_GC: Counters = Counters::default();
+    static ref LAST_GC: Mutex<ReferenceProcessingStats> =
+        Mutex::new(ReferenceProcessingStats::default());
+}
+
+fn kind_index(ty: ReferenceType) -> Option<usize> {
+    match ty {
+        ReferenceType::Soft => Some(0),
+        ReferenceType::Weak => Some(1),
+        ReferenceType::Final => Some(2),
+        ReferenceType::Phantom => Some(3),
+        ReferenceType::None | ReferenceType::Other => None,
+    }
+}
+
+fn reference_type<const COMPRESSED: bool>(reference: ObjectReference) -> ReferenceType {
+    let oop = Oop::from(reference);
+    unsafe { oop.klass::<COMPRESSED>().cast::<InstanceKlass>() }.reference_type
+}
+
+fn count(counters: &[AtomicUsize; NUM_KINDS], ty: ReferenceType, n: usize) {
+    if let Some(index) = kind_index(ty) {
+        counters[index].fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+pub fn record_discovered(ty: ReferenceType) {
+    count(&CURRENT_GC.discovered, ty, 1);
+}
+
+pub fn record_cleared<const COMPRESSED: bool>(reference: ObjectReference) {
+    count(
+        &CURRENT_GC.cleared,
+        reference_type::<COMPRESSED>(reference),
+        1,
+    );
+}
+
+pub fn record_retained<const COMPRESSED: bool>(reference: ObjectReference) {
+    count(
+        &CURRENT_GC.retained,
+        reference_type::<COMPRESSED>(reference),
+        1,
+    );
+}
+
+pub fn record_enqueued<const COMPRESSED: bool>(references: &[ObjectReference]) {
+    for reference in references {
+        count(
+            &CURRENT_GC.enqueued,
+            reference_type::<COMPRESSED>(*reference),
+            1,
+        );
+    }
+}
+
+/// Collect the counts of the GC that just finished, and reset the counters for the next GC.
+pub fn finish_gc() {
+    let take = |counters: &[AtomicUsize; NUM_KINDS], index: usize| {
+        counters[index].swap(0, Ordering::Relaxed)
+    };
+    let mut kinds = [ReferenceStats::default(); NUM_KINDS];
+    for (index, stats) in kinds.iter_mut().enumerate() {
+        let discovered = take(&CURRENT_GC.discovered, index);
+        let cleared = take(&CURRENT_GC.cleared, index);
+        let retained = take(&CURRENT_GC.retained, index);
+        let enqueued = take(&CURRENT_GC.enqueued, index);
+        *stats = ReferenceStats {
+            discovered,
+            cleared,
+            retained,
+            enqueued,
+        };
+        probe!(
+            mmtk_openjdk,
+            reference_stats,
+            index,
+            discovered,
+            cleared,
+            retained,
+            enqueued
+        );
+    }
+    let [soft, weak, final_, phantom] = kinds;
+    *LAST_GC.lock().unwrap() = ReferenceProcessingStats {
+        soft,
+        weak,
+        final_,
+        phantom,
+    };
+}
+
+/// The counts of the last GC.
+pub fn last_gc_stats() -> ReferenceProcessingStats {
+    *LAST_GC.lock().unwrap()
+}
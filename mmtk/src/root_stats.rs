@@ -0,0 +1,104 @@
+//! Per-GC counts of root slots by category.
+//!
+//! Root slots reported by the VM through `SlotsClosure` are counted for the category of the work
+//! packet that is scanning them on the current thread.  Code cache roots are counted by
+//! `ScanCodeCacheRoots` itself, because they come from the binding's remembered sets.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// The categories of roots.  The names are used in the GC event log, and the indices in the
+/// `scan_vm_roots` probes (see `tools/tracing/timeline/visualize_openjdk.py`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RootCategory {
+    ThreadStacks,
+    #[cfg_attr(not(feature = "conservative_stack_scanning"), allow(dead_code))]
+    ConservativeStacks,
+    Universe,
+    JNIHandles,
+    ObjectSynchronizer,
+    Management,
+    JvmtiExport,
+    AOTLoader,
+    SystemDictionary,
+    CodeCacheNursery,
+    CodeCacheMature,
+    StringTable,
+    ClassLoaderDataGraph,
+    WeakProcessor,
+    VMThread,
+    Finalization,
+}
+
+const NUM_CATEGORIES: usize = 16;
+
+pub const ROOT_CATEGORY_NAMES: [&str; NUM_CATEGORIES] = [
+    "thread_stacks",
+    "conservative_stacks",
+    "universe",
+    "jni_handles",
+    "object_synchronizer",
+    "management",
+    "jvmti_export",
+    "aot_loader",
+    "system_dictionary",
+    "code_cache_nursery",
+    "code_cache_mature",
+    "string_table",
+    "class_loader_data_graph",
+    "weak_processor",
+    "vm_thread",
+    "finalization",
+];
+
+/// The number of root slots of each category, indexed by `RootCategory as usize`.
+pub type RootCounts = [usize; NUM_CATEGORIES];
+
+lazy_static! {
+    static ref CURRENT_GC: [AtomicUsize; NUM_CATEGORIES] = Default::default();
+}
+static LAST_GC: Mutex<RootCounts> = Mutex::new([0; NUM_CATEGORIES]);
+
+thread_local! {
+    /// The category of the roots being scanned by the current thread.
+    static CURRENT_CATEGORY: Cell<Option<RootCategory>> = const { Cell::new(None) };
+    /// The number of root slots reported by the VM for the current category so far.
+    static CURRENT_COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Run `f`, and count the root slots reported by the VM in it as `category`.  Return the number
+/// of root slots reported in `f`.
+pub fn with_category(category: RootCategory, f: impl FnOnce()) -> usize {
+    let previous = CURRENT_CATEGORY.replace(Some(category));
+    let previous_count = CURRENT_COUNT.replace(0);
+    f();
+    CURRENT_CATEGORY.set(previous);
+    CURRENT_COUNT.replace(previous_count)
+}
+
+/// Count `n` root slots reported by the VM for the current category.
+pub fn record_reported(n: usize) {
+    if let Some(category) = CURRENT_CATEGORY.get() {
+        record(category, n);
+        CURRENT_COUNT.set(CURRENT_COUNT.get() + n);
+    }
+}
+
+pub fn record(category: RootCategory, n: usize) {
+    CURRENT_GC[category as usize].fetch_add(n, Ordering::Relaxed);
+}
+
+/// Collect the counts of the GC that just finished, and reset the counters for the next GC.
+pub fn finish_gc() {
+    let mut counts = [0; NUM_CATEGORIES];
+    for (count, counter) in counts.iter_mut().zip(CURRENT_GC.iter()) {
+        *count = counter.swap(0, Ordering::Relaxed);
+    }
+    *LAST_GC.lock().unwrap() = counts;
+}
+
+/// The counts of the last GC.
+pub fn last_gc_counts() -> RootCounts {
+    *LAST_GC.lock().unwrap()
+}
@@ -1,12 +1,13 @@
 use crate::gc_work::*;
+use crate::root_stats::{with_category, RootCategory};
 use crate::Slot;
 use crate::{NewBuffer, OpenJDKSlot, UPCALLS};
-use crate::{OpenJDK, SlotsClosure};
+use crate::{OpenJDK, RootsKind, SlotsClosure};
 use mmtk::memory_manager;
-use mmtk::scheduler::WorkBucketStage;
+use mmtk::scheduler::{GCWorker, WorkBucketStage};
 use mmtk::util::opaque_pointer::*;
 use mmtk::util::{Address, ObjectReference};
-use mmtk::vm::{RootsWorkFactory, Scanning, SlotVisitor};
+use mmtk::vm::{ObjectTracerContext, RootsWorkFactory, Scanning, SlotVisitor};
 use mmtk::Mutator;
 use mmtk::MutatorContext;
 
@@ -18,14 +19,24 @@ extern "C" fn report_slots_and_renew_buffer<S: Slot, F: RootsWorkFactory<S>>(
     ptr: *mut Address,
     length: usize,
     capacity: usize,
+    kind: RootsKind,
     factory_ptr: *mut libc::c_void,
 ) -> NewBuffer {
     if !ptr.is_null() {
+        crate::root_stats::record_reported(length);
         // Note: Currently OpenJDKSlot has the same layout as Address.  If the layout changes, we
         // should fix the Rust-to-C interface.
         let buf = unsafe { Vec::<S>::from_raw_parts(ptr as _, length, capacity) };
         let factory: &mut F = unsafe { &mut *(factory_ptr as *mut F) };
-        factory.create_process_roots_work(buf);
+        match kind {
+            RootsKind::Normal => factory.create_process_roots_work(buf),
+            // Pinned objects are not moved, so the slots never need updating.  Just report the
+            // objects.
+            RootsKind::Pinning => factory.create_process_pinning_roots_work(load_slots(buf)),
+            RootsKind::TransitivelyPinning => {
+                factory.create_process_tpinning_roots_work(load_slots(buf))
+            }
+        }
     }
     let (ptr, _, capacity) = {
         // TODO: Use Vec::into_raw_parts() when the method is available.
@@ -37,6 +48,10 @@ extern "C" fn report_slots_and_renew_buffer<S: Slot, F: RootsWorkFactory<S>>(
     NewBuffer { ptr, capacity }
 }
 
+fn load_slots<S: Slot>(slots: Vec<S>) -> Vec<ObjectReference> {
+    slots.into_iter().filter_map(|slot| slot.load()).collect()
+}
+
 pub(crate) fn to_slots_closure<S: Slot, F: RootsWorkFactory<S>>(factory: &mut F) -> SlotsClosure {
     SlotsClosure {
         func: report_slots_and_renew_buffer::<S, F>,
@@ -63,10 +78,17 @@ impl<const COMPRESSED: bool> Scanning<OpenJDK<COMPRESSED>> for VMScanning {
         mutator: &'static mut Mutator<OpenJDK<COMPRESSED>>,
         mut factory: impl RootsWorkFactory<OpenJDKSlot<COMPRESSED>>,
     ) {
+        let _span = crate::trace_recorder::span("ScanMutatorRoots", "roots");
+        let _timer = crate::harness::work_timer();
         let tls = mutator.get_tls();
-        unsafe {
+        let mutator_ptr = mutator as *mut Mutator<OpenJDK<COMPRESSED>> as usize;
+        probe!(mmtk_openjdk, scan_mutator_roots_begin, mutator_ptr);
+        let slots = with_category(RootCategory::ThreadStacks, || unsafe {
             ((*UPCALLS).scan_roots_in_mutator_thread)(to_slots_closure(&mut factory), tls);
-        }
+        });
+        probe!(mmtk_openjdk, scan_mutator_roots_end, mutator_ptr, slots);
+        #[cfg(feature = "conservative_stack_scanning")]
+        scan_stack_conservatively(tls, &mut factory);
     }
 
     fn scan_vm_specific_roots(
@@ -85,6 +107,7 @@ impl<const COMPRESSED: bool> Scanning<OpenJDK<COMPRESSED>> for VMScanning {
                 Box::new(ScanAOTLoaderRoots::new(factory.clone())) as _,
                 Box::new(ScanSystemDictionaryRoots::new(factory.clone())) as _,
                 Box::new(ScanCodeCacheRoots::new(factory.clone())) as _,
+                Box::new(ScanFinalizationRoots::new(factory.clone())) as _,
                 Box::new(ScanStringTableRoots::new(factory.clone())) as _,
                 Box::new(ScanClassLoaderDataGraphRoots::new(factory.clone())) as _,
                 Box::new(ScanWeakProcessorRoots::new(factory.clone())) as _,
@@ -94,7 +117,9 @@ impl<const COMPRESSED: bool> Scanning<OpenJDK<COMPRESSED>> for VMScanning {
     }
 
     fn supports_return_barrier() -> bool {
-        unimplemented!()
+        // JDK 11 has no stack watermarks to hang a return barrier on, so every GC scans
+        // whole thread stacks.
+        false
     }
 
     fn prepare_for_roots_re_scanning() {
@@ -102,4 +127,85 @@ impl<const COMPRESSED: bool> Scanning<OpenJDK<COMPRESSED>> for VMScanning {
             ((*UPCALLS).prepare_for_roots_re_scanning)();
         }
     }
+
+    fn process_weak_refs(
+        worker: &mut GCWorker<OpenJDK<COMPRESSED>>,
+        tracer_context: impl ObjectTracerContext<OpenJDK<COMPRESSED>>,
+    ) -> bool {
+        let _span = crate::trace_recorder::span("ProcessWeakRefs", "work");
+        let _timer = crate::harness::work_timer();
+        let on_stack_nmethods = std::mem::take(&mut *crate::ON_STACK_NMETHODS.lock().unwrap());
+        // Oops in nmethods are only weak in full-heap GCs of generational plans.
+        let is_code_cache_weak = is_code_cache_weak(crate::singleton::<COMPRESSED>());
+        if is_code_cache_weak && !on_stack_nmethods.is_empty() {
+            // Keep nmethods on the stack alive first, and come back to process other nmethods
+            // after the transitive closure from their oops is computed.
+            tracer_context.with_tracer(worker, |tracer| {
+                keep_nmethods_alive::<COMPRESSED>(&on_stack_nmethods, tracer)
+            });
+            return true;
+        }
+        // Finalization may resurrect objects, so it must be done before nmethods are unloaded.
+        #[cfg(feature = "final_references")]
+        if crate::reference_processor::process_references::<COMPRESSED>(worker, &tracer_context) {
+            return true;
+        }
+        if is_code_cache_weak {
+            tracer_context.with_tracer(worker, |tracer| {
+                process_weak_code_cache_roots::<COMPRESSED>(tracer)
+            });
+        }
+        false
+    }
+
+    fn forward_weak_refs(
+        worker: &mut GCWorker<OpenJDK<COMPRESSED>>,
+        tracer_context: impl ObjectTracerContext<OpenJDK<COMPRESSED>>,
+    ) {
+        if !is_code_cache_weak(crate::singleton::<COMPRESSED>()) {
+            return;
+        }
+        let _span = crate::trace_recorder::span("ForwardWeakRefs", "work");
+        let _timer = crate::harness::work_timer();
+        tracer_context.with_tracer(worker, |tracer| {
+            forward_weak_code_cache_roots::<COMPRESSED>(tracer)
+        });
+    }
+}
+
+/// Conservatively scan the stack of a mutator thread, in addition to the exact roots reported by
+/// the VM.  Native code may hold raw oops that are not described by any oop map.  Every word that
+/// points to an object (according to the VO bit) is reported as a pinning root, because we do not
+/// know if the word is really a reference, and cannot update it.
+#[cfg(feature = "conservative_stack_scanning")]
+fn scan_stack_conservatively<const COMPRESSED: bool>(
+    tls: VMMutatorThread,
+    factory: &mut impl RootsWorkFactory<OpenJDKSlot<COMPRESSED>>,
+) {
+    use mmtk::util::constants::BYTES_IN_ADDRESS;
+
+    let mut start = Address::ZERO;
+    let mut end = Address::ZERO;
+    unsafe {
+        ((*UPCALLS).get_conservative_stack_range)(tls, &mut start, &mut end);
+    }
+
+    let mut objects = vec![];
+    let mut num_objects = 0;
+    let mut cursor = start.align_up(BYTES_IN_ADDRESS);
+    while cursor < end {
+        let word = unsafe { cursor.load::<Address>() };
+        if let Some(object) = memory_manager::is_mmtk_object(word) {
+            objects.push(object);
+            num_objects += 1;
+            if objects.len() >= WORK_PACKET_CAPACITY {
+                factory.create_process_pinning_roots_work(std::mem::take(&mut objects));
+            }
+        }
+        cursor += BYTES_IN_ADDRESS;
+    }
+    if !objects.is_empty() {
+        factory.create_process_pinning_roots_work(objects);
+    }
+    crate::root_stats::record(RootCategory::ConservativeStacks, num_objects);
 }
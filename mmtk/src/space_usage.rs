@@ -0,0 +1,94 @@
+//! The usage of each MMTk space, for the memory pools of `java.lang.management`.
+//!
+//! The spaces of a plan do not change after MMTk is created, and `Plan::for_each_space` always
+//! visits them in the same order, so a space is identified by its index in that order.
+
+use crate::OpenJDK;
+use libc::c_char;
+use mmtk::policy::space::Space;
+use mmtk::util::conversions;
+
+/// The maximum length of a space name, including the terminating NUL.
+const MAX_SPACE_NAME: usize = 32;
+
+/// The usage of a space.  Must match `SpaceUsage` in `mmtk.h`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SpaceUsage {
+    /// The name of the space as a NUL-terminated string, e.g. "nursery" or "los".
+    pub name: [c_char; MAX_SPACE_NAME],
+    /// The bytes of the pages reserved by the space.
+    pub used_bytes: usize,
+    /// The bytes of the pages mapped for the space.  Pages are reserved before they are
+    /// committed, so this may be smaller than `used_bytes`.
+    pub committed_bytes: usize,
+    /// The maximum heap size, or the size of the address range of a contiguous space if that is
+    /// smaller.
+    pub max_bytes: usize,
+}
+
+impl SpaceUsage {
+    /// The name of the space.
+    pub fn name(&self) -> &str {
+        let name = unsafe { std::ffi::CStr::from_ptr(self.name.as_ptr()) };
+        name.to_str().unwrap_or("?")
+    }
+
+    fn new<const COMPRESSED: bool>(space: &dyn Space<OpenJDK<COMPRESSED>>) -> Self {
+        let mut name = [0; MAX_SPACE_NAME];
+        for (dst, src) in name[..MAX_SPACE_NAME - 1]
+            .iter_mut()
+            .zip(space.get_name().bytes())
+        {
+            *dst = src as c_char;
+        }
+        let common = space.common();
+        let pr = space.get_page_resource();
+        SpaceUsage {
+            name,
+            used_bytes: conversions::pages_to_bytes(pr.reserved_pages()),
+            committed_bytes: conversions::pages_to_bytes(pr.committed_pages()),
+            max_bytes: if common.contiguous {
+                common.extent.min(crate::gc_trigger::max_heap_bytes())
+            } else {
+                crate::gc_trigger::max_heap_bytes()
+            },
+        }
+    }
+}
+
+/// Call `f` with the index and the usage of each space.
+pub fn for_each_space_usage(mut f: impl FnMut(usize, SpaceUsage)) {
+    fn visit<const COMPRESSED: bool>(f: &mut dyn FnMut(usize, SpaceUsage)) {
+        let mut index = 0;
+        crate::singleton::<COMPRESSED>()
+            .get_plan()
+            .for_each_space(&mut |space| {
+                f(index, SpaceUsage::new(space));
+                index += 1;
+            });
+    }
+    if crate::use_compressed_oops() {
+        visit::<true>(&mut f)
+    } else {
+        visit::<false>(&mut f)
+    }
+}
+
+/// The number of spaces.
+pub fn num_spaces() -> usize {
+    let mut n = 0;
+    for_each_space_usage(|_, _| n += 1);
+    n
+}
+
+/// The usage of the space at `index`.
+pub fn space_usage(index: usize) -> Option<SpaceUsage> {
+    let mut result = None;
+    for_each_space_usage(|i, usage| {
+        if i == index {
+            result = Some(usage);
+        }
+    });
+    result
+}
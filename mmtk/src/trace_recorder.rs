@@ -0,0 +1,244 @@
+//! An in-process recorder of GC events in the Chrome trace event format, which can be opened with
+//! Perfetto (<https://ui.perfetto.dev>) or `chrome://tracing`.
+//!
+//! Unlike `tools/tracing/timeline`, this needs neither root nor bpftrace.  It is enabled by the
+//! environment variable `MMTK_TRACE_FILE=<path>`.  The file uses the JSON array format, and events
+//! are appended to it at `harness_end` and when the VM exits, so a trace cut short by a crash can
+//! still be opened.
+//!
+//! The binding records each GC, stopping and resuming mutators, its own work packets (root
+//! scanning, and processing weak references), and scanning the roots of each mutator thread.  The
+//! work packets of mmtk-core are not visible to the binding, so they are not recorded.
+//!
+//! Each thread appends events to its own buffer, so recording an event does not contend with
+//! other threads.  Writing the trace moves the events out of the buffers.
+
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+#[derive(Clone, Copy)]
+enum Phase {
+    /// A complete event with a duration.
+    Complete { duration_ns: u64 },
+    /// The beginning of an async event, which may end on another thread.
+    AsyncBegin { id: usize },
+    /// The end of an async event.
+    AsyncEnd { id: usize },
+}
+
+struct Event {
+    name: &'static str,
+    category: &'static str,
+    phase: Phase,
+    /// The time since `EPOCH`.
+    timestamp_ns: u64,
+}
+
+struct ThreadBuffer {
+    tid: usize,
+    events: Mutex<Vec<Event>>,
+}
+
+/// The trace file, and what has been written to it.
+struct TraceFile {
+    path: String,
+    file: File,
+    /// The number of buffers in `BUFFERS` whose thread names have been written.
+    named_buffers: usize,
+    /// True if nothing has been written after the opening bracket.
+    empty: bool,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NEXT_TID: AtomicUsize = AtomicUsize::new(1);
+static TRACE_FILE: Mutex<Option<TraceFile>> = Mutex::new(None);
+
+lazy_static! {
+    static ref EPOCH: Instant = Instant::now();
+    static ref BUFFERS: Mutex<Vec<Arc<ThreadBuffer>>> = Mutex::new(vec![]);
+}
+
+thread_local! {
+    static BUFFER: RefCell<Option<Arc<ThreadBuffer>>> = const { RefCell::new(None) };
+}
+
+/// Lock a mutex of the recorder.  Writing the trace at exit should not fail because a thread
+/// panicked while holding the lock.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Enable the recorder if `MMTK_TRACE_FILE` is set.
+pub fn init() {
+    let Ok(path) = std::env::var("MMTK_TRACE_FILE") else {
+        return;
+    };
+    let file = match File::create(&path).and_then(|mut file| {
+        file.write_all(b"[\n")?;
+        Ok(file)
+    }) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Failed to create the MMTk trace {}: {}", path, e);
+            return;
+        }
+    };
+    *lock(&TRACE_FILE) = Some(TraceFile {
+        path,
+        file,
+        named_buffers: 0,
+        empty: true,
+    });
+    lazy_static::initialize(&EPOCH);
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn timestamp_ns(time: Instant) -> u64 {
+    time.saturating_duration_since(*EPOCH).as_nanos() as u64
+}
+
+fn record(event: Event) {
+    BUFFER.with_borrow_mut(|buffer| {
+        let buffer = buffer.get_or_insert_with(|| {
+            let buffer = Arc::new(ThreadBuffer {
+                tid: NEXT_TID.fetch_add(1, Ordering::Relaxed),
+                events: Mutex::new(vec![]),
+            });
+            lock(&BUFFERS).push(buffer.clone());
+            buffer
+        });
+        lock(&buffer.events).push(event);
+    });
+}
+
+/// Records a complete event from its creation to its drop.
+#[must_use]
+pub struct Span {
+    name: &'static str,
+    category: &'static str,
+    start: Instant,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        record(Event {
+            name: self.name,
+            category: self.category,
+            phase: Phase::Complete {
+                duration_ns: self.start.elapsed().as_nanos() as u64,
+            },
+            timestamp_ns: timestamp_ns(self.start),
+        });
+    }
+}
+
+/// Start a span on the current thread.  Return `None` if the recorder is disabled.
+pub fn span(name: &'static str, category: &'static str) -> Option<Span> {
+    is_enabled().then(|| Span {
+        name,
+        category,
+        start: Instant::now(),
+    })
+}
+
+/// Begin an async event identified by `name` and `id`, which may end on another thread.
+pub fn async_begin(name: &'static str, category: &'static str, id: usize) {
+    if is_enabled() {
+        record(Event {
+            name,
+            category,
+            phase: Phase::AsyncBegin { id },
+            timestamp_ns: timestamp_ns(Instant::now()),
+        });
+    }
+}
+
+/// End an async event started by `async_begin`.
+pub fn async_end(name: &'static str, category: &'static str, id: usize) {
+    if is_enabled() {
+        record(Event {
+            name,
+            category,
+            phase: Phase::AsyncEnd { id },
+            timestamp_ns: timestamp_ns(Instant::now()),
+        });
+    }
+}
+
+fn write_event(w: &mut String, pid: u32, tid: usize, event: &Event) {
+    let ts_us = event.timestamp_ns as f64 / 1000.0;
+    let _ = write!(
+        w,
+        "{{\"name\":\"{}\",\"cat\":\"{}\",\"pid\":{},\"tid\":{},\"ts\":{:.3}",
+        event.name, event.category, pid, tid, ts_us,
+    );
+    let _ = match event.phase {
+        Phase::Complete { duration_ns } => {
+            write!(
+                w,
+                ",\"ph\":\"X\",\"dur\":{:.3}}}",
+                duration_ns as f64 / 1000.0
+            )
+        }
+        Phase::AsyncBegin { id } => write!(w, ",\"ph\":\"b\",\"id\":{}}}", id),
+        Phase::AsyncEnd { id } => write!(w, ",\"ph\":\"e\",\"id\":{}}}", id),
+    };
+}
+
+/// Append the events recorded since the last call to the trace file.
+pub fn write_trace() {
+    let mut trace = lock(&TRACE_FILE);
+    let Some(trace) = trace.as_mut() else {
+        return;
+    };
+    let pid = std::process::id();
+    let mut out = String::new();
+    let mut separate = |out: &mut String| {
+        if !std::mem::replace(&mut trace.empty, false) {
+            out.push_str(",\n");
+        }
+    };
+    let buffers = lock(&BUFFERS);
+    for (index, buffer) in buffers.iter().enumerate() {
+        if index >= trace.named_buffers {
+            separate(&mut out);
+            let _ = write!(
+                out,
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":\"Thread {}\"}}}}",
+                pid, buffer.tid, buffer.tid,
+            );
+        }
+        let events = std::mem::take(&mut *lock(&buffer.events));
+        for event in events.iter() {
+            separate(&mut out);
+            write_event(&mut out, pid, buffer.tid, event);
+        }
+    }
+    trace.named_buffers = buffers.len();
+    drop(buffers);
+    if let Err(e) = trace.file.write_all(out.as_bytes()) {
+        eprintln!("Failed to write the MMTk trace to {}: {}", trace.path, e);
+    }
+}
+
+/// Write the remaining events, close the array, and stop recording.  Called when the VM exits.
+pub fn finish() {
+    if !ENABLED.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    write_trace();
+    if let Some(mut trace) = lock(&TRACE_FILE).take() {
+        if let Err(e) = trace.file.write_all(b"\n]\n") {
+            eprintln!("Failed to write the MMTk trace to {}: {}", trace.path, e);
+        }
+    }
+}
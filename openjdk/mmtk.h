@@ -90,6 +90,117 @@ extern size_t get_max_non_los_default_alloc_bytes();
  */
 extern void add_finalizer(void* obj);
 extern void* get_finalized_object();
+extern size_t mmtk_get_finalized_objects(void** buf, size_t capacity);
+#ifdef MMTK_ENABLE_FINAL_REFERENCES
+extern void mmtk_count_registered_finalizer();
+extern bool mmtk_defer_finalizer_registration(void* obj);
+extern size_t mmtk_take_finalizers_to_register(void** buf, size_t capacity);
+#endif
+
+typedef struct {
+    size_t registered;
+    size_t pending;
+    size_t finalized;
+} FinalizationStats;
+
+extern FinalizationStats mmtk_get_finalization_stats();
+
+typedef struct {
+    size_t discovered;
+    size_t cleared;
+    size_t retained;
+    size_t enqueued;
+} ReferenceStats;
+
+typedef struct {
+    ReferenceStats soft;
+    ReferenceStats weak;
+    ReferenceStats final_;
+    ReferenceStats phantom;
+} ReferenceProcessingStats;
+
+// Reference processing statistics of the last GC.
+extern ReferenceProcessingStats mmtk_get_reference_stats();
+
+/**
+ * GC results and the GC overhead limit
+ */
+typedef struct {
+    size_t index;
+    bool full_heap;
+    uint64_t pause_ns;
+    uint64_t mutator_ns;
+    size_t used_bytes_before;
+    size_t used_bytes_after;
+    size_t total_bytes;
+} GCResult;
+
+typedef struct {
+    size_t requested_bytes;
+    int semantics;
+    size_t used_bytes;
+    size_t free_bytes;
+    size_t total_bytes;
+    size_t max_heap_bytes;
+    GCResult last_gc;
+} OutOfMemoryReport;
+
+extern GCResult mmtk_get_last_gc_result();
+extern void mmtk_set_gc_overhead_limit(size_t gc_time_limit, size_t gc_heap_free_limit, size_t threshold);
+extern bool mmtk_gc_overhead_limit_exceeded();
+
+/**
+ * Heap iteration
+ */
+#ifdef MMTK_ENABLE_VO_BIT
+// Call `callback` for each object in the heap.  Must be called at a safepoint.
+extern void mmtk_enumerate_objects(void (*callback)(void* object, void* data), void* data);
+extern bool mmtk_is_object(void* addr);
+// Return the object that contains `addr`, or NULL if `addr` is not inside an object.
+extern void* mmtk_find_object_from_internal_pointer(void* addr);
+#endif
+
+/**
+ * Heap printing
+ */
+// Print the heap into `buf` as a NUL-terminated string.  Safe to call when reporting crashes.
+// GC thread states are not included.  Use MMTkHeap::print_gc_threads_on for them.
+extern size_t mmtk_print_heap_info(char* buf, size_t capacity);
+
+/**
+ * Memory pools
+ */
+typedef struct {
+    char name[32];
+    size_t used_bytes;
+    size_t committed_bytes;
+    size_t max_bytes;
+} SpaceUsage;
+
+extern size_t mmtk_num_spaces();
+extern bool mmtk_get_space_usage(size_t index, SpaceUsage* usage);
+extern bool mmtk_is_generational_plan();
+
+/**
+ * GC notifications
+ */
+typedef struct {
+    size_t id;
+    const char* cause;
+    bool full_heap;
+    uint64_t duration_ns;
+    size_t num_spaces;
+    const SpaceUsage* before;
+    // NULL when the GC starts.
+    const SpaceUsage* after;
+} GCNotification;
+
+/**
+ * Periodic GC
+ */
+extern uint64_t mmtk_periodic_gc_interval();
+extern bool mmtk_should_trigger_periodic_gc();
+extern void mmtk_release_free_memory();
 
 /**
  * Misc
@@ -98,10 +209,18 @@ extern char* mmtk_active_barrier();
 extern void initialize_collection(void *tls);
 extern void gc_init(size_t heap_size);
 extern bool will_never_move(void* object);
+#ifdef MMTK_ENABLE_OBJECT_PINNING
+extern bool mmtk_supports_object_pinning();
+extern bool mmtk_pin_object(void* object);
+extern bool mmtk_unpin_object(void* object);
+extern bool mmtk_is_pinned(void* object);
+#endif
+extern bool mmtk_supports_pinning_roots();
 extern bool process(char* name, char* value);
 extern bool process_bulk(char* options);
 extern void scan_region();
 extern void handle_user_collection_request(void *tls);
+extern void mmtk_handle_collection_request(void *tls, const char* cause, bool full_heap, bool force);
 
 extern void start_control_collector(void *tls, void *context);
 extern void start_worker(void *tls, void* worker);
@@ -109,6 +228,8 @@ extern void start_worker(void *tls, void* worker);
 extern size_t mmtk_add_nmethod_oop(void* object);
 extern size_t mmtk_register_nmethod(void* nm);
 extern size_t mmtk_unregister_nmethod(void* nm);
+extern void mmtk_report_on_stack_nmethod(void* nm);
+extern bool mmtk_is_reachable(void* object);
 
 /**
  * VM Accounting
@@ -130,12 +251,32 @@ struct MutatorClosure {
     }
 };
 
+// HotSpot tag sets for MMTk log records.  Must match `LogTags` in the Rust code.
+typedef enum {
+    MMTK_LOG_GC = 0,
+    MMTK_LOG_GC_HEAP = 1,
+    MMTK_LOG_GC_REF = 2,
+    MMTK_LOG_GC_PHASES = 3,
+    MMTK_LOG_GC_INIT = 4,
+} MMTkLogTags;
+
+// How the objects referred by a buffer of root slots are treated.  Must match `RootsKind` in
+// the Rust code.
+typedef enum {
+    // The objects may be moved, and the slots will be updated.
+    ROOTS_KIND_NORMAL = 0,
+    // The objects will not be moved, but their children may be.
+    ROOTS_KIND_PINNING = 1,
+    // Neither the objects nor any object reachable from them will be moved.
+    ROOTS_KIND_TRANSITIVELY_PINNING = 2,
+} RootsKind;
+
 struct SlotsClosure {
-    NewBuffer (*func)(void** buf, size_t size, size_t capa, void* data);
+    NewBuffer (*func)(void** buf, size_t size, size_t capa, RootsKind kind, void* data);
     void* data;
 
-    NewBuffer invoke(void** buf, size_t size, size_t capa) {
-        return func(buf, size, capa, data);
+    NewBuffer invoke(void** buf, size_t size, size_t capa, RootsKind kind) {
+        return func(buf, size, capa, kind, data);
     }
 };
 
@@ -147,7 +288,7 @@ typedef struct {
     void (*resume_mutators) (void *tls);
     void (*spawn_gc_thread) (void *tls, int kind, void *ctx);
     void (*block_for_gc) ();
-    void (*out_of_memory) (void *tls, MMTkAllocationError err_kind);
+    void (*out_of_memory) (void *tls, MMTkAllocationError err_kind, const OutOfMemoryReport* report);
     void (*get_mutators) (MutatorClosure closure);
     void (*scan_object) (void* trace, void* object, void* tls);
     void (*dump_object) (void* object);
@@ -155,7 +296,7 @@ typedef struct {
     void* (*get_mmtk_mutator) (void* tls);
     bool (*is_mutator) (void* tls);
     void (*harness_begin) ();
-    void (*harness_end) ();
+    void (*harness_end) (size_t id);
     size_t (*compute_klass_mem_layout_checksum) ();
     int (*offset_of_static_fields) ();
     int (*static_oop_field_count_offset) ();
@@ -180,12 +321,28 @@ typedef struct {
     void (*schedule_finalizer)();
     void (*prepare_for_roots_re_scanning)();
     void (*enqueue_references)(void** objects, size_t len);
+    void (*fix_nmethod_relocations)(void* nm);
+    void (*unload_nmethod)(void* nm, void* cause);
+    void (*get_conservative_stack_range)(void* tls, void** start, void** end);
+    int (*soft_ref_timestamp_offset) ();
+    int64_t (*soft_ref_clock) ();
+    int64_t (*soft_ref_lru_policy_ms_per_mb) ();
+    bool (*should_clear_all_soft_refs) ();
+    void (*notify_gc_start) (const GCNotification* notification);
+    void (*notify_gc_end) (const GCNotification* notification);
+    // `level` is 1 (error) to 5 (trace).
+    bool (*log_is_enabled) (int level, MMTkLogTags tags);
+    void (*log_message) (int level, MMTkLogTags tags, const char* message);
 } OpenJDK_Upcalls;
 
 extern void openjdk_gc_init(OpenJDK_Upcalls *calls);
 extern bool openjdk_is_gc_initialized();
 
 extern bool mmtk_set_heap_size(size_t min, size_t max);
+extern void mmtk_set_heap_free_ratios(size_t min, size_t max);
+// Change the soft and hard heap limits at run time.  Needs MMTK_RUNTIME_HEAP_LIMITS=1.
+extern bool mmtk_set_heap_limits(size_t soft, size_t hard);
+extern size_t mmtk_soft_max_heap_size();
 
 extern bool mmtk_enable_compressed_oops();
 extern void* mmtk_narrow_oop_base();
@@ -213,7 +370,8 @@ extern void add_soft_candidate(void* ref, void* referent);
 extern void add_phantom_candidate(void* ref, void* referent);
 
 extern void mmtk_harness_begin_impl();
-extern void mmtk_harness_end_impl();
+extern void mmtk_harness_end_impl(size_t id);
+extern void mmtk_shutdown();
 
 extern void mmtk_builder_read_env_var_settings();
 extern void mmtk_builder_set_threads(size_t value);
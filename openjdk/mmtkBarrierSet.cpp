@@ -27,6 +27,7 @@
 #include "barriers/mmtkObjectBarrier.hpp"
 #include "mmtkBarrierSet.hpp"
 #include "mmtkBarrierSetAssembler_x86.hpp"
+#include "mmtkUpcalls.hpp"
 #include "runtime/interfaceSupport.inline.hpp"
 #ifdef COMPILER1
 #include "mmtkBarrierSetC1.hpp"
@@ -103,6 +104,12 @@ void MMTkBarrierSet::resize_covered_region(MemRegion new_region) {
 }
 
 
+void MMTkBarrierSet::on_thread_create(Thread* thread) {
+#ifdef MMTK_ENABLE_CONSERVATIVE_STACK_SCANNING
+  MMTkThreadLocalData::data(thread)->blocked_stack_top = NULL;
+#endif
+}
+
 void MMTkBarrierSet::on_thread_destroy(Thread* thread) {
   thread->third_party_heap_mutator.flush();
   thread->third_party_heap_mutator.destroy();
@@ -134,6 +134,7 @@ public:
     return ((MMTkBarrierSet*) BarrierSet::barrier_set())->_runtime;
   }
 
+  virtual void on_thread_create(Thread* thread);
   virtual void on_thread_destroy(Thread* thread);
   virtual void on_thread_attach(JavaThread* thread);
   virtual void on_thread_detach(JavaThread* thread);
@@ -0,0 +1,63 @@
+/*
+ * Copyright (c) 2011, 2018, Oracle and/or its affiliates. All rights reserved.
+ * DO NOT ALTER OR REMOVE COPYRIGHT NOTICES OR THIS FILE HEADER.
+ *
+ * This code is free software; you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License version 2 only, as
+ * published by the Free Software Foundation.
+ *
+ * This code is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+ * version 2 for more details (a copy is included in the LICENSE file that
+ * accompanied this code).
+ *
+ * You should have received a copy of the GNU General Public License version
+ * 2 along with this work; if not, write to the Free Software Foundation,
+ * Inc., 51 Franklin St, Fifth Floor, Boston, MA 02110-1301 USA.
+ *
+ * Please contact Oracle, 500 Oracle Parkway, Redwood Shores, CA 94065 USA
+ * or visit www.oracle.com if you need additional information or have any
+ * questions.
+ *
+ */
+
+#include "precompiled.hpp"
+#include "memory/resourceArea.hpp"
+#include "mmtk.h"
+#include "mmtkDiagnosticCommands.hpp"
+
+MMTkSetHeapLimitsDCmd::MMTkSetHeapLimitsDCmd(outputStream* output, bool heap) :
+  DCmdWithParser(output, heap),
+  _soft("soft", "The soft heap limit", "MEMORY SIZE", true),
+  _hard("hard", "The hard heap limit, which cannot exceed -Xmx", "MEMORY SIZE", true) {
+  _dcmdparser.add_dcmd_argument(&_soft);
+  _dcmdparser.add_dcmd_argument(&_hard);
+}
+
+int MMTkSetHeapLimitsDCmd::num_arguments() {
+  ResourceMark rm;
+  MMTkSetHeapLimitsDCmd* dcmd = new MMTkSetHeapLimitsDCmd(NULL, false);
+  if (dcmd != NULL) {
+    DCmdMark mark(dcmd);
+    return dcmd->_dcmdparser.num_arguments();
+  } else {
+    return 0;
+  }
+}
+
+void MMTkSetHeapLimitsDCmd::execute(DCmdSource source, TRAPS) {
+  size_t soft = (size_t) _soft.value()._size;
+  size_t hard = (size_t) _hard.value()._size;
+  if (mmtk_set_heap_limits(soft, hard)) {
+    output()->print_cr("Heap limits set: soft " SIZE_FORMAT "B, hard " SIZE_FORMAT "B", soft, hard);
+  } else {
+    output()->print_cr("Heap limits not changed.  They must be between -Xms and -Xmx with soft <= "
+                       "hard, and MMTK_RUNTIME_HEAP_LIMITS=1 must be set.");
+  }
+}
+
+void mmtk_register_dcmds() {
+  uint32_t full_export = DCmd_Source_Internal | DCmd_Source_AttachAPI | DCmd_Source_MBean;
+  DCmdFactory::register_DCmdFactory(new DCmdFactoryImpl<MMTkSetHeapLimitsDCmd>(full_export, true, false));
+}
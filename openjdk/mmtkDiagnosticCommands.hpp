@@ -0,0 +1,55 @@
+/*
+ * Copyright (c) 2011, 2018, Oracle and/or its affiliates. All rights reserved.
+ * DO NOT ALTER OR REMOVE COPYRIGHT NOTICES OR THIS FILE HEADER.
+ *
+ * This code is free software; you can redistribute it and/or modify it
+ * under the terms of the GNU General Public License version 2 only, as
+ * published by the Free Software Foundation.
+ *
+ * This code is distributed in the hope that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+ * FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+ * version 2 for more details (a copy is included in the LICENSE file that
+ * accompanied this code).
+ *
+ * You should have received a copy of the GNU General Public License version
+ * 2 along with this work; if not, write to the Free Software Foundation,
+ * Inc., 51 Franklin St, Fifth Floor, Boston, MA 02110-1301 USA.
+ *
+ * Please contact Oracle, 500 Oracle Parkway, Redwood Shores, CA 94065 USA
+ * or visit www.oracle.com if you need additional information or have any
+ * questions.
+ *
+ */
+
+#ifndef MMTK_OPENJDK_MMTK_DIAGNOSTIC_COMMANDS_HPP
+#define MMTK_OPENJDK_MMTK_DIAGNOSTIC_COMMANDS_HPP
+
+#include "services/diagnosticArgument.hpp"
+#include "services/diagnosticFramework.hpp"
+
+// `jcmd <pid> MMTk.set_heap_limits soft=<size> hard=<size>` changes the heap limits of a running
+// VM.  OpenJDK 11 does not have the manageable `SoftMaxHeapSize` flag, so this is the only way to
+// reach `mmtk_set_heap_limits` from outside the VM.
+class MMTkSetHeapLimitsDCmd : public DCmdWithParser {
+protected:
+  DCmdArgument<MemorySizeArgument> _soft;
+  DCmdArgument<MemorySizeArgument> _hard;
+public:
+  MMTkSetHeapLimitsDCmd(outputStream* output, bool heap);
+  static const char* name() { return "MMTk.set_heap_limits"; }
+  static const char* description() {
+    return "Change the soft and hard heap limits. Needs MMTK_RUNTIME_HEAP_LIMITS=1.";
+  }
+  static const char* impact() { return "Low"; }
+  static const JavaPermission permission() {
+    JavaPermission p = {"java.lang.management.ManagementPermission", "control", NULL};
+    return p;
+  }
+  static int num_arguments();
+  virtual void execute(DCmdSource source, TRAPS);
+};
+
+void mmtk_register_dcmds();
+
+#endif // MMTK_OPENJDK_MMTK_DIAGNOSTIC_COMMANDS_HPP
@@ -25,10 +25,13 @@
 #include "precompiled.hpp"
 #include "classfile/stringTable.hpp"
 #include "classfile/symbolTable.hpp"
+#include "logging/log.hpp"
+#include "memory/universe.hpp"
 #include "mmtk.h"
 #include "mmtkFinalizerThread.hpp"
 #include "oops/oop.inline.hpp"
 #include "prims/jvmtiImpl.hpp"
+#include "runtime/handles.inline.hpp"
 #include "runtime/interfaceSupport.inline.hpp"
 #include "runtime/javaCalls.hpp"
 #include "runtime/mutex.hpp"
@@ -42,6 +45,9 @@
 
 MMTkFinalizerThread* MMTkFinalizerThread::instance = NULL;
 
+// The number of objects fetched from MMTk at a time.
+static const size_t FINALIZE_BATCH_SIZE = 256;
+
 void MMTkFinalizerThread::initialize() {
   EXCEPTION_MARK;
 
@@ -85,6 +91,10 @@ void MMTkFinalizerThread::initialize() {
 void MMTkFinalizerThread::finalizer_thread_entry(JavaThread* thread, TRAPS) {
   MMTkFinalizerThread* this_thread = MMTkFinalizerThread::instance;
   while (true) {
+#ifdef MMTK_ENABLE_FINAL_REFERENCES
+    register_finalizers(thread);
+#endif
+
     // Wait until scheduled
     {
       ThreadBlockInVM tbivm(thread);
@@ -95,34 +105,106 @@ void MMTkFinalizerThread::finalizer_thread_entry(JavaThread* thread, TRAPS) {
       this_thread->is_scheduled = false; // Consume this request so we can accept the next.
     }
 
+    GCCause::Cause cause;
+    {
+      MutexLockerEx mu(this_thread->m, Mutex::_no_safepoint_check_flag);
+      cause = this_thread->requested_cause;
+      this_thread->requested_cause = GCCause::_no_gc;
+    }
+    if (cause != GCCause::_no_gc) {
+      Universe::heap()->collect(cause);
+    }
+
     // finalize objects
     while (true) {
-      void* obj_ref = get_finalized_object();
-      if (obj_ref != NULL) {
-        instanceOop obj = (instanceOop) obj_ref;
-
-        // Invoke finalize()
-        {
-          HandleMark hm;
-          JavaValue ret(T_VOID);
-          instanceHandle handle_obj(this_thread, obj);
-          TempNewSymbol finalize_method = SymbolTable::new_symbol("finalize", this_thread);
-          Symbol* sig = vmSymbols::void_method_signature();
-
-          JavaCalls::call_virtual(&ret, handle_obj, obj->klass(), finalize_method, sig, this_thread);
-        }
-      } else {
+      void* objs[FINALIZE_BATCH_SIZE];
+      size_t n = mmtk_get_finalized_objects(objs, FINALIZE_BATCH_SIZE);
+      if (n == 0) {
         break;
       }
+
+      HandleMark hm;
+      // MMTk no longer keeps these objects.  Create handles for all of them before running any
+      // finalizer, because a finalizer may trigger GC.
+      instanceHandle handles[FINALIZE_BATCH_SIZE];
+      for (size_t i = 0; i < n; i++) {
+        handles[i] = instanceHandle(this_thread, (instanceOop) objs[i]);
+      }
+
+      // Invoke finalize()
+      TempNewSymbol finalize_method = SymbolTable::new_symbol("finalize", this_thread);
+      Symbol* sig = vmSymbols::void_method_signature();
+      for (size_t i = 0; i < n; i++) {
+        JavaValue ret(T_VOID);
+        JavaCalls::call_virtual(&ret, handles[i], handles[i]->klass(), finalize_method, sig, this_thread);
+      }
     }
   }
 }
 
 MMTkFinalizerThread::MMTkFinalizerThread(ThreadFunction entry_point) : JavaThread(entry_point) {
   this->is_scheduled = false;
+  this->requested_cause = GCCause::_no_gc;
   this->m = new Monitor(Mutex::suspend_resume, "mmtk-finalizer-monitor", true, Monitor::_safepoint_check_never);
 }
 
+#ifdef MMTK_ENABLE_FINAL_REFERENCES
+void MMTkFinalizerThread::notify_finalizers_to_register() {
+  MMTkFinalizerThread* thread = instance;
+  if (thread == NULL) {
+    return;
+  }
+  MutexLockerEx mu(thread->m, Mutex::_no_safepoint_check_flag);
+  if (!thread->is_scheduled) {
+    thread->is_scheduled = true;
+    thread->m->notify();
+  }
+}
+
+void MMTkFinalizerThread::register_finalizers(JavaThread* thread) {
+  while (true) {
+    void* objs[FINALIZE_BATCH_SIZE];
+    size_t n = mmtk_take_finalizers_to_register(objs, FINALIZE_BATCH_SIZE);
+    if (n == 0) {
+      break;
+    }
+
+    HandleMark hm;
+    // MMTk no longer keeps these objects.  Create handles for all of them before calling into
+    // Java, which may trigger GC.
+    instanceHandle handles[FINALIZE_BATCH_SIZE];
+    for (size_t i = 0; i < n; i++) {
+      handles[i] = instanceHandle(thread, (instanceOop) objs[i]);
+    }
+
+    methodHandle mh(thread, Universe::finalizer_register_method());
+    for (size_t i = 0; i < n; i++) {
+      JavaValue result(T_VOID);
+      JavaCallArguments args(handles[i]);
+      JavaCalls::call(&result, mh, &args, thread);
+      if (thread->has_pending_exception()) {
+        // The object will not be finalized.  There is no caller to rethrow the exception to.
+        log_warning(gc)("MMTk: Failed to register a finalizable object");
+        thread->clear_pending_exception();
+      } else {
+        mmtk_count_registered_finalizer();
+      }
+    }
+  }
+}
+#endif
+
+void MMTkFinalizerThread::request_collection(GCCause::Cause cause) {
+  MMTkFinalizerThread* thread = instance;
+  guarantee(thread != NULL, "The MMTk finalizer thread is created before collection is enabled");
+  MutexLockerEx mu(thread->m, Mutex::_no_safepoint_check_flag);
+  thread->requested_cause = cause;
+  if (!thread->is_scheduled) {
+    thread->is_scheduled = true;
+    thread->m->notify();
+  }
+}
+
 void MMTkFinalizerThread::schedule() {
   assert(!Thread::current()->is_Java_thread(), "Supposed to be called by GC thread. Actually called by JavaThread.");
   MutexLockerEx mu(this->m, Mutex::_no_safepoint_check_flag);
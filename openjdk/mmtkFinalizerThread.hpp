@@ -25,6 +25,7 @@
 #ifndef MMTK_OPENJDK_MMTK_FINALIZER_THREAD_HPP
 #define MMTK_OPENJDK_MMTK_FINALIZER_THREAD_HPP
 
+#include "gc/shared/gcCause.hpp"
 #include "runtime/mutex.hpp"
 #include "runtime/perfData.hpp"
 #include "runtime/thread.hpp"
@@ -42,12 +43,24 @@ private:
   }
 public:
   bool is_scheduled;
+  // The cause of a collection requested by the VM thread, or `GCCause::_no_gc`.
+  GCCause::Cause requested_cause;
   Monitor* m;
   static MMTkFinalizerThread* instance;
   static void initialize();
   static void finalizer_thread_entry(JavaThread* thread, TRAPS);
 
   void schedule();
+  // Ask the thread to run `CollectedHeap::collect(cause)`.  Called by the VM thread, which cannot
+  // run an MMTk GC itself.  Returns without waiting for the GC.
+  static void request_collection(GCCause::Cause cause);
+#ifdef MMTK_ENABLE_FINAL_REFERENCES
+  // Wake up the thread to register finalizable objects deferred by register_finalizer(void*).
+  // Called by Java threads.  Objects deferred before the thread starts are registered when it
+  // starts.
+  static void notify_finalizers_to_register();
+  static void register_finalizers(JavaThread* thread);
+#endif
 };
 
 #endif // MMTK_OPENJDK_MMTK_FINALIZER_THREAD_HPP
@@ -34,18 +34,22 @@
 #include "logging/log.hpp"
 #include "memory/resourceArea.hpp"
 #include "mmtk.h"
+#include "mmtkDiagnosticCommands.hpp"
 #include "mmtkHeap.hpp"
 #include "mmtkMutator.hpp"
+#include "mmtkPeriodicGCThread.hpp"
 #include "mmtkUpcalls.hpp"
 #include "mmtkVMCompanionThread.hpp"
 #include "oops/oop.inline.hpp"
 #include "runtime/atomic.hpp"
 #include "runtime/handles.inline.hpp"
 #include "runtime/java.hpp"
+#include "runtime/safepoint.hpp"
 #include "runtime/thread.hpp"
 #include "runtime/vmThread.hpp"
 #include "services/management.hpp"
 #include "services/memoryManager.hpp"
+#include "services/memoryService.hpp"
 #include "services/memTracker.hpp"
 #include "utilities/vmError.hpp"
 /*
@@ -69,6 +73,9 @@ MMTkHeap::MMTkHeap(MMTkCollectorPolicy* policy) :
   _collector_policy(policy),
   _num_root_scan_tasks(0),
   _n_workers(0),
+  _collector_threads(new (ResourceObj::C_HEAP, mtGC) GrowableArray<Thread*>(4, true, mtGC)),
+  _companion_thread(NULL),
+  _mmtk_nursery_manager(NULL),
   _gc_lock(new Monitor(Mutex::safepoint, "MMTkHeap::_gc_lock", true, Monitor::_safepoint_check_never)),
   _soft_ref_policy()
 {
@@ -108,8 +115,18 @@ jint MMTkHeap::initialize() {
   // Set heap size
   bool set_heap_size = mmtk_set_heap_size(min_heap_size, max_heap_size);
   guarantee(set_heap_size, "Failed to set MMTk heap size. Please check if the heap size is valid: min = %ld, max = %ld\n", min_heap_size, max_heap_size);
+  mmtk_set_heap_free_ratios(MinHeapFreeRatio, MaxHeapFreeRatio);
 
   openjdk_gc_init(&mmtk_upcalls);
+#ifdef MMTK_ENABLE_CONSERVATIVE_STACK_SCANNING
+  if (!mmtk_supports_pinning_roots()) {
+    vm_exit_during_initialization("The selected MMTk plan does not support conservative stack scanning",
+                                  "Use a plan that can pin objects, such as Immix, StickyImmix or MarkSweep");
+  }
+#endif
+  if (UseGCOverheadLimit) {
+    mmtk_set_gc_overhead_limit(GCTimeLimit, GCHeapFreeLimit, AdaptiveSizePolicyGCTimeLimitThreshold);
+  }
   // Cache the value here. It is a constant depending on the selected plan. The plan won't change from now, so value won't change.
   MMTkMutatorContext::max_non_los_default_alloc_bytes = get_max_non_los_default_alloc_bytes();
 
@@ -180,6 +197,7 @@ void MMTkHeap::post_initialize() {
   if (UseCompressedOops) {
     mmtk_set_compressed_klass_base_and_shift((void*) Universe::narrow_klass_base(), (size_t) Universe::narrow_klass_shift());
   }
+  mmtk_register_dcmds();
 }
 
 void MMTkHeap::enable_collection() {
@@ -187,9 +205,19 @@ void MMTkHeap::enable_collection() {
   // Otherwise it is possible that we schedule finalizer (during a GC) before the finalizer thread is ready.
   MMTkFinalizerThread::initialize();
 
+  if (mmtk_periodic_gc_interval() > 0) {
+    MMTkPeriodicGCThread::initialize();
+  }
+
   ::initialize_collection(0);
 }
 
+// Called by before_exit() when the VM exits.  Print the harness summary and finish the trace here
+// rather than in atexit handlers, which may run while other threads still hold the binding's locks.
+void MMTkHeap::stop() {
+  mmtk_shutdown();
+}
+
 ////Previously pure abstract methods--
 
 size_t MMTkHeap::capacity() const {
@@ -244,6 +272,21 @@ bool MMTkHeap::is_in_reserved(const void* p) const {
   return is_in(p);
 }
 
+#ifdef MMTK_ENABLE_OBJECT_PINNING
+bool MMTkHeap::supports_object_pinning() const {
+  return mmtk_supports_object_pinning();
+}
+
+oop MMTkHeap::pin_object(JavaThread* thread, oop obj) {
+  mmtk_pin_object((void*) obj);
+  return obj;
+}
+
+void MMTkHeap::unpin_object(JavaThread* thread, oop obj) {
+  mmtk_unpin_object((void*) obj);
+}
+#endif
+
 bool MMTkHeap::supports_tlab_allocation() const {
   //returning false is good enough...used in universe.cpp
   return false;
@@ -282,16 +325,51 @@ bool MMTkHeap::card_mark_must_follow_store() const { //OK
   return false;
 }
 
-void MMTkHeap::collect(GCCause::Cause cause) {//later when gc is implemented in rust
-  handle_user_collection_request((MMTk_Mutator) &Thread::current()->third_party_heap_mutator);
-  // guarantee(false, "collect not supported");
+void MMTkHeap::collect(GCCause::Cause cause) {
+  bool full_heap = true;
+  bool clear_soft_refs = false;
+  switch (cause) {
+    case GCCause::_java_lang_system_gc:
+      // JVM_GC() checks DisableExplicitGC, but System.gc() may reach here by other paths.
+      if (DisableExplicitGC) {
+        log_debug(gc)("Ignored collection request: %s", GCCause::to_string(cause));
+        return;
+      }
+      break;
+    case GCCause::_wb_young_gc:
+    case GCCause::_scavenge_alot:
+      full_heap = false;
+      break;
+    case GCCause::_wb_full_gc:
+    case GCCause::_metadata_GC_clear_soft_refs:
+    case GCCause::_last_ditch_collection:
+      clear_soft_refs = true;
+      break;
+    default:
+      // e.g. heap inspection, heap dump, JVMTI ForceGarbageCollection, and reaching the
+      // metaspace threshold, which needs a full-heap GC to unload classes.
+      break;
+  }
+
+  log_info(gc)("Collection requested: %s", GCCause::to_string(cause));
+  if (clear_soft_refs) {
+    _soft_ref_policy.set_should_clear_all_soft_refs(true);
+  }
+  // Only System.gc() is subject to the MMTk option `ignore_system_gc`.  The VM needs the other
+  // requests, e.g. to unload classes or to inspect the heap, so they are forced.
+  bool force = cause != GCCause::_java_lang_system_gc;
+  mmtk_handle_collection_request((MMTk_Mutator) &Thread::current()->third_party_heap_mutator,
+                                 GCCause::to_string(cause), full_heap, force);
 }
 
 // Perform a full collection
-void MMTkHeap::do_full_collection(bool clear_all_soft_refs) {//later when gc is implemented in rust
-  // guarantee(false, "do full collection not supported");
-
-  // handle_user_collection_request((MMTk_Mutator) &Thread::current()->third_party_heap_mutator);
+void MMTkHeap::do_full_collection(bool clear_all_soft_refs) {
+  // HotSpot calls this from VM operations, i.e. on the VM thread inside a safepoint.  An MMTk GC
+  // cannot run there: it needs the VM thread to run VM_MMTkSTWOperation, and it blocks the
+  // requesting mutator, which the VM thread is not.  Let the MMTk finalizer thread request the GC
+  // through collect() instead.  The GC happens after the VM operation returns.
+  GCCause::Cause cause = clear_all_soft_refs ? GCCause::_metadata_GC_clear_soft_refs : gc_cause();
+  MMTkFinalizerThread::request_collection(cause);
 }
 
 
@@ -302,41 +380,69 @@ SoftRefPolicy* MMTkHeap::soft_ref_policy() {return &_soft_ref_policy;}//OK
 
 GrowableArray<GCMemoryManager*> MMTkHeap::memory_managers() {//may cause error
 
-  GrowableArray<GCMemoryManager*> memory_managers(1);
+  GrowableArray<GCMemoryManager*> memory_managers(2);
   memory_managers.append(_mmtk_manager);
+  if (_mmtk_nursery_manager != NULL) {
+    memory_managers.append(_mmtk_nursery_manager);
+  }
   return memory_managers;
 }
 GrowableArray<MemoryPool*> MMTkHeap::memory_pools() {//may cause error
 
-  GrowableArray<MemoryPool*> memory_pools(1);
-  memory_pools.append(_mmtk_pool);
+  GrowableArray<MemoryPool*> memory_pools(_mmtk_pools->length());
+  for (int i = 0; i < _mmtk_pools->length(); i++) {
+    memory_pools.append(_mmtk_pools->at(i));
+  }
   return memory_pools;
 }
 
-// Iterate over all objects, calling "cl.do_object" on each.
-void MMTkHeap::object_iterate(ObjectClosure* cl) { //No need to implement.Traced whole path.Only other heaps call it.
-  guarantee(false, "object iterate not supported");
+#ifdef MMTK_ENABLE_VO_BIT
+static void mmtk_object_iterate_callback(void* object, void* data) {
+  ((ObjectClosure*) data)->do_object((oop) object);
 }
+#endif
 
-// Similar to object_iterate() except iterates only
-// over live objects.
-void MMTkHeap::safe_object_iterate(ObjectClosure* cl) { //not sure..many dependencies from vm
-  guarantee(false, "safe object iterate not supported");
+// Iterate over all objects, calling "cl.do_object" on each.  Used by heap dumps, heap histograms
+// and JVMTI heap iteration.  Objects are found with the VO bit.
+void MMTkHeap::object_iterate(ObjectClosure* cl) {
+#ifdef MMTK_ENABLE_VO_BIT
+  assert(SafepointSynchronize::is_at_safepoint(), "must be at a safepoint");
+  mmtk_enumerate_objects(mmtk_object_iterate_callback, cl);
+#else
+  guarantee(false, "object iterate requires the VO bit.  Build with MMTK_VO_BIT=1.");
+#endif
 }
 
-HeapWord* MMTkHeap::block_start(const void* addr) const {//OK
-  guarantee(false, "block start not supported");
+// Similar to object_iterate() except iterates only
+// over live objects.
+void MMTkHeap::safe_object_iterate(ObjectClosure* cl) {
+  // All objects found by the VO bit are safe to visit at a safepoint.
+  object_iterate(cl);
+}
+
+// A "block" is an object.  Addresses outside objects are not in any block.  Used by error
+// reporting and the serviceability agent to describe arbitrary addresses.
+HeapWord* MMTkHeap::block_start(const void* addr) const {
+#ifdef MMTK_ENABLE_VO_BIT
+  return (HeapWord*) mmtk_find_object_from_internal_pointer((void*) addr);
+#else
+  guarantee(false, "block start requires the VO bit.  Build with MMTK_VO_BIT=1.");
   return NULL;
+#endif
 }
 
-size_t MMTkHeap::block_size(const HeapWord* addr) const { //OK
-  guarantee(false, "block size not supported");
-  return 0;
+size_t MMTkHeap::block_size(const HeapWord* addr) const {
+  assert(block_is_obj(addr), "not the start of an object: " PTR_FORMAT, p2i(addr));
+  return oop(addr)->size();
 }
 
-bool MMTkHeap::block_is_obj(const HeapWord* addr) const { //OK
-  guarantee(false, "block is obj not supported");
+bool MMTkHeap::block_is_obj(const HeapWord* addr) const {
+#ifdef MMTK_ENABLE_VO_BIT
+  return mmtk_is_object((void*) addr);
+#else
+  guarantee(false, "block is obj requires the VO bit.  Build with MMTK_VO_BIT=1.");
   return false;
+#endif
 }
 
 jlong MMTkHeap::millis_since_last_gc() {//later when gc is implemented in rust
@@ -358,23 +464,115 @@ void MMTkHeap::prepare_for_verify() {
 void MMTkHeap::initialize_serviceability() {//OK
 
 
-  _mmtk_pool = new MMTkMemoryPool(_start, _end,  "MMTk pool",collector_policy()->min_heap_byte_size(), false);
+  _mmtk_manager = new GCMemoryManager("MMTk GC", "end of major GC");
+  if (mmtk_is_generational_plan()) {
+    _mmtk_nursery_manager = new GCMemoryManager("MMTk Nursery GC", "end of minor GC");
+  }
 
-  _mmtk_manager = new GCMemoryManager("MMTk GC", "end of GC");
-  _mmtk_manager->add_pool(_mmtk_pool);
+  // One memory pool for each MMTk space, e.g. "MMTk nursery" and "MMTk los".
+  size_t num_spaces = mmtk_num_spaces();
+  _mmtk_pools = new (ResourceObj::C_HEAP, mtGC) GrowableArray<MMTkMemoryPool*>((int) num_spaces, true, mtGC);
+  for (size_t i = 0; i < num_spaces; i++) {
+    SpaceUsage usage;
+    if (!mmtk_get_space_usage(i, &usage)) {
+      break;
+    }
+    size_t name_len = strlen("MMTk ") + strlen(usage.name) + 1;
+    char* name = NEW_C_HEAP_ARRAY(char, name_len, mtGC);
+    jio_snprintf(name, name_len, "MMTk %s", usage.name);
+    MMTkMemoryPool* pool = new MMTkMemoryPool(i, name, 0, usage.max_bytes, false);
+    _mmtk_pools->append(pool);
+    _mmtk_manager->add_pool(pool);
+    // A nursery GC may also change the usage of mature spaces, e.g. by promoting objects.
+    if (_mmtk_nursery_manager != NULL) {
+      _mmtk_nursery_manager->add_pool(pool);
+    }
+  }
+}
+
+GCMemoryManager* MMTkHeap::memory_manager_for(const GCNotification* notification) const {
+  if (!notification->full_heap && _mmtk_nursery_manager != NULL) {
+    return _mmtk_nursery_manager;
+  }
+  return _mmtk_manager;
+}
+
+// The GCCause of a GC, for GC notifications.  Causes that are not HotSpot causes are reported as
+// _no_cause_specified.
+static GCCause::Cause mmtk_gc_cause(const char* cause) {
+  for (int i = 0; i < GCCause::_last_gc_cause; i++) {
+    GCCause::Cause c = (GCCause::Cause) i;
+    if (strcmp(cause, GCCause::to_string(c)) == 0) {
+      return c;
+    }
+  }
+  return GCCause::_no_cause_specified;
+}
+
+void MMTkHeap::notify_gc_start(const GCNotification* notification) {
+  log_debug(gc)("GC(" SIZE_FORMAT ") started: %s, %s", notification->id, notification->cause,
+                notification->full_heap ? "full heap" : "nursery");
+  MemoryService::gc_begin(memory_manager_for(notification),
+                          true /* recordGCBeginTime */, true /* recordAccumulatedGCTime */,
+                          true /* recordPreGCUsage */, true /* recordPeakUsage */);
+}
+
+void MMTkHeap::notify_gc_end(const GCNotification* notification) {
+  _last_gc_time = os::javaTimeNanos() / NANOSECS_PER_MILLISEC;
+  MemoryService::gc_end(memory_manager_for(notification),
+                        true /* recordPostGCUsage */, true /* recordAccumulatedGCTime */,
+                        true /* recordGCEndTime */, true /* countCollection */,
+                        mmtk_gc_cause(notification->cause), true /* allMemoryPoolsAffected */);
+
+  log_debug(gc)("GC(" SIZE_FORMAT ") ended: %s, %s, " UINT64_FORMAT "us", notification->id,
+                notification->cause, notification->full_heap ? "full heap" : "nursery",
+                notification->duration_ns / 1000);
+  LogTarget(Debug, gc, heap) lt;
+  if (lt.is_enabled()) {
+    for (size_t i = 0; i < notification->num_spaces; i++) {
+      const SpaceUsage* before = &notification->before[i];
+      const SpaceUsage* after = &notification->after[i];
+      lt.print("GC(" SIZE_FORMAT ") %s: " SIZE_FORMAT "K->" SIZE_FORMAT "K", notification->id,
+               before->name, before->used_bytes / K, after->used_bytes / K);
+    }
+  }
 }
 
 // Print heap information on the given outputStream.
-void MMTkHeap::print_on(outputStream* st) const {guarantee(false, "print on not supported");}
+void MMTkHeap::print_on(outputStream* st) const {
+  // This is called when reporting crashes.  Print into a buffer on the stack instead of
+  // allocating memory.
+  char buf[4096];
+  mmtk_print_heap_info(buf, sizeof(buf));
+  st->print_raw(buf);
+}
 
 
 // Print all GC threads (other than the VM thread)
 // used by this heap.
-void MMTkHeap::print_gc_threads_on(outputStream* st) const {guarantee(false, "print gc threads on not supported");}
+class MMTkPrintThreadClosure : public ThreadClosure {
+  outputStream* _st;
+public:
+  MMTkPrintThreadClosure(outputStream* st) : _st(st) {}
+  void do_thread(Thread* thread) {
+    thread->print_on(_st);
+    _st->cr();
+  }
+};
+
+void MMTkHeap::print_gc_threads_on(outputStream* st) const {
+  MMTkPrintThreadClosure cl(st);
+  gc_threads_do(&cl);
+}
 
 // Iterator for all GC threads (other than VM thread)
 void MMTkHeap::gc_threads_do(ThreadClosure* tc) const {
-  // guarantee(false, "gc threads do not supported");
+  if (_companion_thread != NULL) {
+    tc->do_thread(_companion_thread);
+  }
+  for (int i = 0; i < _collector_threads->length(); i++) {
+    tc->do_thread(_collector_threads->at(i));
+  }
 }
 
 // Print any relevant tracing info that flags imply.
@@ -498,12 +696,27 @@ void MMTkHeap::scan_roots(OopClosure& cl) {
   WeakProcessor::oops_do(&cl);
 }
 
+// Like ParallelGC, fail one allocation after the GC overhead limit is exceeded.  The VM then throws
+// OutOfMemoryError("GC overhead limit exceeded").
+static bool mmtk_check_gc_overhead_limit(bool* gc_overhead_limit_was_exceeded) {
+  if (UseGCOverheadLimit && mmtk_gc_overhead_limit_exceeded()) {
+    log_info(gc)("MMTk: GC overhead limit exceeded");
+    *gc_overhead_limit_was_exceeded = true;
+    return true;
+  }
+  return false;
+}
+
+// A NULL result means the allocation failed even after mmtk-core's emergency collection, which it
+// triggers by itself before giving up.  The VM then throws OutOfMemoryError without another GC.
 HeapWord* MMTkHeap::mem_allocate(size_t size, bool* gc_overhead_limit_was_exceeded) {
+  if (mmtk_check_gc_overhead_limit(gc_overhead_limit_was_exceeded)) return NULL;
   HeapWord* obj = Thread::current()->third_party_heap_mutator.alloc(size << LogHeapWordSize);
   return obj;
 }
 
 HeapWord* MMTkHeap::mem_allocate_nonmove(size_t size, bool* gc_overhead_limit_was_exceeded) {
+  if (mmtk_check_gc_overhead_limit(gc_overhead_limit_was_exceeded)) return NULL;
   return Thread::current()->third_party_heap_mutator.alloc(size << LogHeapWordSize, AllocatorLos);
 }
 
@@ -49,12 +49,15 @@ class MMTkVMCompanionThread;
 class MMTkHeap : public CollectedHeap {
   MMTkCollectorPolicy* _collector_policy;
   SoftRefPolicy _soft_ref_policy;
-  MMTkMemoryPool* _mmtk_pool;
+  GrowableArray<MMTkMemoryPool*>* _mmtk_pools;
   GCMemoryManager* _mmtk_manager;
+  // Only used for nursery GCs of generational plans.  Full-heap GCs are reported by _mmtk_manager.
+  GCMemoryManager* _mmtk_nursery_manager;
   HeapWord* _start;
   HeapWord* _end;
   static MMTkHeap* _heap;
   size_t _n_workers;
+  GrowableArray<Thread*>* _collector_threads;
   Monitor* _gc_lock;
   ContiguousSpace* _space;
   int _num_root_scan_tasks;
@@ -73,6 +76,7 @@ public:
 
   jint initialize();
   void enable_collection();
+  virtual void stop();
 
   virtual HeapWord* mem_allocate(size_t size, bool* gc_overhead_limit_was_exceeded);
   HeapWord* mem_allocate_nonmove(size_t size, bool* gc_overhead_limit_was_exceeded);
@@ -100,6 +104,12 @@ public:
   bool is_in_reserved(const void* p) const;
   bool supports_tlab_allocation() const;
 
+#ifdef MMTK_ENABLE_OBJECT_PINNING
+  bool supports_object_pinning() const;
+  oop pin_object(JavaThread* thread, oop obj);
+  void unpin_object(JavaThread* thread, oop obj);
+#endif
+
   bool supports_inline_contig_alloc() const {
     return MMTK_ENABLE_ALLOCATION_FASTPATH;
   }
@@ -110,8 +120,9 @@ public:
   // The amount of used space for thread-local allocation buffers for the given thread.
   size_t tlab_used(Thread *thr) const;
 
-  void new_collector_thread() {
+  void new_collector_thread(Thread* thread) {
     _n_workers += 1;
+    _collector_threads->append(thread);
   }
 
   Monitor* gc_lock() {
@@ -157,11 +168,17 @@ public:
 
   void prepare_for_verify() ;
 
+  // Report the start and the end of a GC to the memory managers, for GarbageCollectorMXBean.
+  void notify_gc_start(const GCNotification* notification);
+  void notify_gc_end(const GCNotification* notification);
+
 
 private:
 
   void initialize_serviceability() ;
 
+  GCMemoryManager* memory_manager_for(const GCNotification* notification) const;
+
   void set_mmtk_options(bool set_defaults);
 
 public:
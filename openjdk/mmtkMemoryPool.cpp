@@ -23,21 +23,31 @@
  */
 
 #include "precompiled.hpp"
+#include "mmtk.h"
 #include "mmtkMemoryPool.hpp"
 
-MMTkMemoryPool::MMTkMemoryPool(HeapWord* start, HeapWord* end,
-                               const char* name, size_t init_size,
+MMTkMemoryPool::MMTkMemoryPool(size_t space_index, const char* name,
+                               size_t init_size, size_t max_size,
                                bool support_usage_threshold) :
-  CollectedMemoryPool(name, init_size,
-                      pointer_delta(start, end)*HeapWordSize, support_usage_threshold),
-            
-        _start(start), _end(end), _used_in_bytes(0){
+  CollectedMemoryPool(name, init_size, max_size, support_usage_threshold),
+  _space_index(space_index) {
+}
+
+size_t MMTkMemoryPool::used_in_bytes() {
+  SpaceUsage usage;
+  return mmtk_get_space_usage(_space_index, &usage) ? usage.used_bytes : 0;
 }
 
 MemoryUsage MMTkMemoryPool::get_memory_usage() {
+  SpaceUsage usage;
+  if (!mmtk_get_space_usage(_space_index, &usage)) {
+    usage.used_bytes = 0;
+    usage.committed_bytes = 0;
+  }
   size_t maxSize   = (available_for_allocation() ? max_size() : 0);
-  size_t used      = used_in_bytes();
-  size_t committed = pointer_delta(_start, _end)*HeapWordSize;
+  size_t used      = usage.used_bytes;
+  // A space reserves pages before it maps them, but MemoryUsage requires used <= committed.
+  size_t committed = MAX2(usage.committed_bytes, used);
 
   return MemoryUsage(initial_size(), used, committed, maxSize);
 }
@@ -9,6 +9,7 @@
 
 class MMTkRootsClosure : public OopClosure {
   SlotsClosure _slots_closure;
+  RootsKind _kind;
   void** _buffer;
   size_t _cap;
   size_t _cursor;
@@ -30,7 +31,7 @@ class MMTkRootsClosure : public OopClosure {
 
   void flush() {
     if (_cursor > 0) {
-      NewBuffer buf = _slots_closure.invoke(_buffer, _cursor, _cap);
+      NewBuffer buf = _slots_closure.invoke(_buffer, _cursor, _cap, _kind);
       _buffer = buf.buf;
       _cap = buf.cap;
       _cursor = 0;
@@ -38,8 +39,9 @@ class MMTkRootsClosure : public OopClosure {
   }
 
 public:
-  MMTkRootsClosure(SlotsClosure slots_closure): _slots_closure(slots_closure), _cursor(0) {
-    NewBuffer buf = slots_closure.invoke(NULL, 0, 0);
+  MMTkRootsClosure(SlotsClosure slots_closure, RootsKind kind = ROOTS_KIND_NORMAL):
+    _slots_closure(slots_closure), _kind(kind), _cursor(0) {
+    NewBuffer buf = slots_closure.invoke(NULL, 0, 0, kind);
     _buffer = buf.buf;
     _cap = buf.cap;
   }
@@ -23,16 +23,22 @@
  */
 
 #include "precompiled.hpp"
+#include "classfile/javaClasses.hpp"
 #include "classfile/stringTable.hpp"
+#include "logging/log.hpp"
 #include "code/nmethod.hpp"
 #include "memory/iterator.inline.hpp"
 #include "memory/resourceArea.hpp"
+#include "memory/universe.hpp"
 #include "mmtkCollectorThread.hpp"
 #include "mmtkHeap.hpp"
+#include "mmtkMutator.hpp"
 #include "mmtkRootsClosure.hpp"
 #include "mmtkUpcalls.hpp"
 #include "mmtkVMCompanionThread.hpp"
 #include "runtime/atomic.hpp"
+#include "runtime/icache.hpp"
+#include "runtime/jniHandles.hpp"
 #include "runtime/mutexLocker.hpp"
 #include "runtime/os.hpp"
 #include "runtime/safepoint.hpp"
@@ -62,10 +68,20 @@ static void mmtk_stop_all_mutators(void *tls, MutatorClosure closure) {
 
   log_debug(gc)("Finished enumerating threads.");
   nmethod::oops_do_marking_prologue();
+  Universe::print_heap_before_gc();
 }
 
 static void mmtk_resume_mutators(void *tls) {
+  Universe::print_heap_after_gc();
   nmethod::oops_do_marking_epilogue();
+  // Like ReferenceProcessor::update_soft_ref_master_clock(), advance the clock of soft references
+  // so that the LRU policy in the next GC sees how long they have not been accessed.
+  java_lang_ref_SoftReference::set_clock(os::javaTimeNanos() / NANOSECS_PER_MILLISEC);
+  SoftRefPolicy* soft_ref_policy = MMTkHeap::heap()->soft_ref_policy();
+  if (soft_ref_policy->should_clear_all_soft_refs()) {
+    soft_ref_policy->set_should_clear_all_soft_refs(false);
+    soft_ref_policy->cleared_all_soft_refs();
+  }
   // ClassLoaderDataGraph::purge();
   CodeCache::gc_epilogue();
   JvmtiExport::gc_epilogue();
@@ -93,8 +109,8 @@ static const int GC_THREAD_KIND_WORKER = 1;
 static void mmtk_spawn_gc_thread(void* tls, int kind, void* ctx) {
   switch (kind) {
     case GC_THREAD_KIND_WORKER: {
-      MMTkHeap::heap()->new_collector_thread();
       MMTkCollectorThread* t = new MMTkCollectorThread(ctx);
+      MMTkHeap::heap()->new_collector_thread(t);
       if (!os::create_thread(t, os::pgc_thread)) {
         printf("Failed to create thread");
         guarantee(false, "panic");
@@ -110,8 +126,6 @@ static void mmtk_spawn_gc_thread(void* tls, int kind, void* ctx) {
 }
 
 static void mmtk_block_for_gc() {
-  MMTkHeap::heap()->_last_gc_time = os::javaTimeNanos() / NANOSECS_PER_MILLISEC;
-
   // We must read the counter before entering safepoint.
   // This thread (or another mutator) has just triggered GC.
   // The GC cannot start until all mutators enter safepoint.
@@ -127,6 +141,14 @@ static void mmtk_block_for_gc() {
 
   log_debug(gc)("Will block until the start_the_world counter reaches %zu.", next_count);
 
+#ifdef MMTK_ENABLE_CONSERVATIVE_STACK_SCANNING
+  // This may be called from native code, e.g. a JNI function that allocates, whose frames and
+  // registers may hold raw oops.  Spill callee-saved registers into this frame, and let the GC
+  // scan the stack from here.  Caller-saved registers are already saved by the callers.
+  __builtin_unwind_init();
+  MMTkThreadLocalData::data(JavaThread::current())->blocked_stack_top = os::current_stack_pointer();
+#endif
+
   {
     // Enter safepoint.
     JavaThread* thread = JavaThread::current();
@@ -141,10 +163,41 @@ static void mmtk_block_for_gc() {
       MMTkHeap::heap()->gc_lock()->wait(Mutex::_no_safepoint_check_flag);
     }
   }
+#ifdef MMTK_ENABLE_CONSERVATIVE_STACK_SCANNING
+  MMTkThreadLocalData::data(JavaThread::current())->blocked_stack_top = NULL;
+#endif
   log_debug(gc)("Resumed after GC finished.");
 }
 
-static void mmtk_out_of_memory(void* tls, MMTkAllocationError err_kind) {
+static const char* mmtk_allocation_semantics_name(int semantics) {
+  switch (semantics) {
+  case AllocatorDefault:  return "default";
+  case AllocatorImmortal: return "immortal";
+  case AllocatorLos:      return "large object";
+  case AllocatorCode:     return "code";
+  case AllocatorReadOnly: return "read-only";
+  default:                return "other";
+  }
+}
+
+static void mmtk_report_out_of_memory(const OutOfMemoryReport* report) {
+  const GCResult& gc = report->last_gc;
+  log_info(gc)("MMTk: Out of memory when allocating " SIZE_FORMAT " bytes in the %s space",
+               report->requested_bytes, mmtk_allocation_semantics_name(report->semantics));
+  log_info(gc)("MMTk: Heap used " SIZE_FORMAT "K, free " SIZE_FORMAT "K, total " SIZE_FORMAT "K, max " SIZE_FORMAT "K",
+               report->used_bytes / K, report->free_bytes / K, report->total_bytes / K, report->max_heap_bytes / K);
+  if (gc.index == 0) {
+    log_info(gc)("MMTk: No GC has happened");
+  } else {
+    log_info(gc)("MMTk: Last GC(" SIZE_FORMAT ") %s: " SIZE_FORMAT "K->" SIZE_FORMAT "K(" SIZE_FORMAT "K), pause %.3fms",
+                 gc.index, gc.full_heap ? "full heap" : "nursery",
+                 gc.used_bytes_before / K, gc.used_bytes_after / K, gc.total_bytes / K,
+                 gc.pause_ns / 1000000.0);
+  }
+}
+
+static void mmtk_out_of_memory(void* tls, MMTkAllocationError err_kind, const OutOfMemoryReport* report) {
+  mmtk_report_out_of_memory(report);
   switch (err_kind) {
   case HeapOutOfMemory :
     // Note that we have to do nothing for the case that the Java heap is too small. Since mmtk-core already
@@ -158,7 +211,7 @@ static void mmtk_out_of_memory(void* tls, MMTkAllocationError err_kind) {
     break;
   case MmapOutOfMemory :
     // Abort the VM immediately due to insufficient system resources.
-    vm_exit_out_of_memory(0, OOM_MMAP_ERROR, "MMTk: Unable to acquire more memory from the OS. Out of system resources.");
+    vm_exit_out_of_memory(report->requested_bytes, OOM_MMAP_ERROR, "MMTk: Unable to acquire more memory from the OS. Out of system resources.");
     break;
   }
 }
@@ -184,11 +237,30 @@ static void mmtk_scan_roots_in_all_mutator_threads(SlotsClosure closure) {
   MMTkHeap::heap()->scan_roots_in_all_mutator_threads(cl);
 }
 
+// Reports nmethods with frames on the stack to MMTk.  Oops in nmethods are weak in full-heap
+// GCs, but nmethods on the stack must not be unloaded.
+class MMTkOnStackNMethodClosure : public CodeBlobClosure {
+public:
+  virtual void do_code_blob(CodeBlob* cb) {
+    nmethod* nm = cb->as_nmethod_or_null();
+    if (nm != NULL && !nm->test_set_oops_do_mark()) {
+      mmtk_report_on_stack_nmethod((void*) nm);
+    }
+  }
+};
+
 static void mmtk_scan_roots_in_mutator_thread(SlotsClosure closure, void* tls) {
   ResourceMark rm;
   JavaThread* thread = (JavaThread*) tls;
+  if (thread->in_critical() && mmtk_supports_pinning_roots()) {
+    // The thread may hold raw pointers from GetPrimitiveArrayCritical or GetStringCritical into
+    // objects referred by its JNI local handles.  MMTk does not honor the GCLocker, so pin them.
+    MMTkRootsClosure pinning_cl(closure, ROOTS_KIND_PINNING);
+    thread->active_handles()->oops_do(&pinning_cl);
+  }
   MMTkRootsClosure cl(closure);
-  thread->oops_do(&cl, NULL);
+  MMTkOnStackNMethodClosure cb_cl;
+  thread->oops_do(&cl, &cb_cl);
 }
 
 static void mmtk_scan_object(void* trace, void* object, void* tls) {
@@ -221,12 +293,12 @@ static void mmtk_harness_begin() {
   mmtk_harness_begin_impl();
 }
 
-static void mmtk_harness_end() {
+static void mmtk_harness_end(size_t id) {
   assert(Thread::current()->is_Java_thread(), "Only Java thread can leave vm");
 
   JavaThread* current = ((JavaThread*) Thread::current());
   ThreadInVMfromNative tiv(current);
-  mmtk_harness_end_impl();
+  mmtk_harness_end_impl(id);
 }
 
 static int offset_of_static_fields() {
@@ -293,6 +365,15 @@ static void mmtk_enqueue_references(void** objects, size_t len) {
     return;
   }
 
+  for (size_t i = 0; i < len; i++) {
+    oop reff = (oop) objects[i];
+    if (InstanceKlass::cast(reff->klass())->reference_type() == REF_FINAL) {
+      // Like ReferenceProcessor::process_final_keep_alive_work(), self-loop `next` to mark the
+      // FinalReference as not active.  Its referent is kept for the finalizer.
+      java_lang_ref_Reference::set_next_raw(reff, reff);
+    }
+  }
+
   oop first = (oop) objects[0]; // This points to the first node of the linked list.
   oop last = first; // This points to the last node of the linked list.
 
@@ -315,6 +396,109 @@ static void mmtk_enqueue_references(void** objects, size_t len) {
   HeapAccess<AS_NO_KEEPALIVE>::oop_store_at(last, java_lang_ref_Reference::discovered_offset, old_first);
 }
 
+static void mmtk_fix_nmethod_relocations(void* nm) {
+  // The GC has updated oops embedded in this nmethod.  Re-patch the instructions from the oop
+  // relocations and flush the instruction cache so that other processors see the new values.
+  nmethod* code = (nmethod*) nm;
+  code->fix_oop_relocations();
+  ICache::invalidate_range(code->code_begin(), code->code_size());
+}
+
+// Whether an object has been reached in the current GC.  Used when unloading nmethods to decide
+// which class loaders are still alive.
+class MMTkIsAliveClosure : public BoolObjectClosure {
+public:
+  bool do_object_b(oop obj) {
+    return obj == NULL || mmtk_is_reachable((void*) obj);
+  }
+};
+
+static void mmtk_unload_nmethod(void* nm, void* cause) {
+  // Some oops embedded in this nmethod are dead, and `cause` is one of them.  MMTk has already
+  // cleared them and removed the nmethod from its CodeCache roots.  Unload it as
+  // nmethod::do_unloading does for other GCs.
+  ResourceMark rm;
+  MMTkIsAliveClosure is_alive;
+  nmethod* code = (nmethod*) nm;
+  code->make_unloaded(&is_alive, (oop) cause);
+}
+
+static void mmtk_get_conservative_stack_range(void* tls, void** start, void** end) {
+  JavaThread* thread = (JavaThread*) tls;
+#ifdef MMTK_ENABLE_CONSERVATIVE_STACK_SCANNING
+  void* blocked_stack_top = MMTkThreadLocalData::data(thread)->blocked_stack_top;
+#else
+  void* blocked_stack_top = NULL;
+#endif
+  if (blocked_stack_top != NULL) {
+    // The thread is blocked in mmtk_block_for_gc.  Scan all its frames, including native frames
+    // below the last Java frame and the callee-saved registers spilled by mmtk_block_for_gc.
+    *start = blocked_stack_top;
+    *end = (void*) thread->stack_base();
+  } else if (thread->has_last_Java_frame()) {
+    // Threads in native code keep running during GC, so their frames below the last Java frame
+    // cannot be scanned.  Only scan native frames that called into Java through JNI, which are
+    // between the last Java frame and the stack base and do not change.
+    *start = (void*) thread->last_Java_sp();
+    *end = (void*) thread->stack_base();
+  } else {
+    *start = NULL;
+    *end = NULL;
+  }
+}
+
+static int mmtk_soft_ref_timestamp_offset() {
+  return java_lang_ref_SoftReference::timestamp_offset;
+}
+
+static int64_t mmtk_soft_ref_clock() {
+  return java_lang_ref_SoftReference::clock();
+}
+
+static int64_t mmtk_soft_ref_lru_policy_ms_per_mb() {
+  return SoftRefLRUPolicyMSPerMB;
+}
+
+static bool mmtk_should_clear_all_soft_refs() {
+  return MMTkHeap::heap()->soft_ref_policy()->should_clear_all_soft_refs();
+}
+
+static void mmtk_notify_gc_start(const GCNotification* notification) {
+  MMTkHeap::heap()->notify_gc_start(notification);
+}
+
+static void mmtk_notify_gc_end(const GCNotification* notification) {
+  MMTkHeap::heap()->notify_gc_end(notification);
+}
+
+static LogTagSet& mmtk_log_tag_set(MMTkLogTags tags) {
+  switch (tags) {
+    case MMTK_LOG_GC_HEAP:   return LogTagSetMapping<LOG_TAGS(gc, heap)>::tagset();
+    case MMTK_LOG_GC_REF:    return LogTagSetMapping<LOG_TAGS(gc, ref)>::tagset();
+    case MMTK_LOG_GC_PHASES: return LogTagSetMapping<LOG_TAGS(gc, phases)>::tagset();
+    case MMTK_LOG_GC_INIT:   return LogTagSetMapping<LOG_TAGS(gc, init)>::tagset();
+    default:                 return LogTagSetMapping<LOG_TAGS(gc)>::tagset();
+  }
+}
+
+static LogLevelType mmtk_log_level(int level) {
+  switch (level) {
+    case 1:  return LogLevel::Error;
+    case 2:  return LogLevel::Warning;
+    case 3:  return LogLevel::Info;
+    case 4:  return LogLevel::Debug;
+    default: return LogLevel::Trace;
+  }
+}
+
+static bool mmtk_log_is_enabled(int level, MMTkLogTags tags) {
+  return mmtk_log_tag_set(tags).is_level(mmtk_log_level(level));
+}
+
+static void mmtk_log_message(int level, MMTkLogTags tags, const char* message) {
+  mmtk_log_tag_set(tags).log(mmtk_log_level(level), message);
+}
+
 OpenJDK_Upcalls mmtk_upcalls = {
   mmtk_stop_all_mutators,
   mmtk_resume_mutators,
@@ -352,5 +536,16 @@ OpenJDK_Upcalls mmtk_upcalls = {
   mmtk_number_of_mutators,
   mmtk_schedule_finalizer,
   mmtk_prepare_for_roots_re_scanning,
-  mmtk_enqueue_references
+  mmtk_enqueue_references,
+  mmtk_fix_nmethod_relocations,
+  mmtk_unload_nmethod,
+  mmtk_get_conservative_stack_range,
+  mmtk_soft_ref_timestamp_offset,
+  mmtk_soft_ref_clock,
+  mmtk_soft_ref_lru_policy_ms_per_mb,
+  mmtk_should_clear_all_soft_refs,
+  mmtk_notify_gc_start,
+  mmtk_notify_gc_end,
+  mmtk_log_is_enabled,
+  mmtk_log_message
 };
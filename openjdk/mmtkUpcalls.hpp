@@ -26,8 +26,22 @@
 #define MMTK_OPENJDK_MMTK_UPCALLS_HPP
 
 #include "mmtk.h"
+#include "runtime/thread.hpp"
 
 extern OpenJDK_Upcalls mmtk_upcalls;
 
+#ifdef MMTK_ENABLE_CONSERVATIVE_STACK_SCANNING
+// The binding's data in Thread::_gc_data.
+struct MMTkThreadLocalData {
+  // The stack pointer of a thread blocked in mmtk_block_for_gc, after spilling callee-saved
+  // registers, or NULL.  Everything from here to the stack base is scanned conservatively.
+  void* blocked_stack_top;
+
+  static MMTkThreadLocalData* data(Thread* thread) {
+    return thread->gc_data<MMTkThreadLocalData>();
+  }
+};
+#endif
+
 #endif // MMTK_OPENJDK_MMTK_UPCALLS_HPP
 
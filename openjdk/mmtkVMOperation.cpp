@@ -26,6 +26,7 @@
 #include "mmtk.h"
 #include "mmtkVMCompanionThread.hpp"
 #include "mmtkVMOperation.hpp"
+#include "gc/shared/isGCActiveMark.hpp"
 #include "logging/log.hpp"
 
 VM_MMTkSTWOperation::VM_MMTkSTWOperation(MMTkVMCompanionThread *companion_thread):
@@ -39,6 +40,8 @@ bool VM_MMTkSTWOperation::doit_prologue() {
 
 void VM_MMTkSTWOperation::doit() {
     log_trace(vmthread)("Entered VM_MMTkSTWOperation::doit().");
+    // nmethod::make_unloaded() expects to be called during a GC.
+    IsGCActiveMark mark;
     _companion_thread->do_mmtk_stw_operation();
     log_trace(vmthread)("Leaving VM_MMTkSTWOperation::doit()");
 }
@@ -1,6 +1,11 @@
 #include "precompiled.hpp"
 #include "gc/shared/thirdPartyHeap.hpp"
+#include "memory/universe.hpp"
 #include "mmtk.h"
+#include "mmtkFinalizerThread.hpp"
+#include "oops/instanceOop.hpp"
+#include "runtime/handles.inline.hpp"
+#include "runtime/javaCalls.hpp"
 #include "thirdPartyHeap.hpp"
 #include "thirdPartyHeapArguments.hpp"
 
@@ -10,8 +15,36 @@ GCArguments* new_gc_arguments() {
   return NULL;
 }
 
+instanceOop register_finalizer(instanceOop obj, TRAPS) {
+#ifdef MMTK_ENABLE_FINAL_REFERENCES
+  // Do what InstanceKlass::register_finalizer() does for other GCs: call Finalizer.register() to
+  // create a FinalReference for the object.  The binding processes FinalReference like other
+  // reference types.  The call may reach a safepoint and move the object, so the caller must use
+  // the returned oop.
+  instanceHandle h_i(THREAD, obj);
+  JavaValue result(T_VOID);
+  JavaCallArguments args(h_i);
+  methodHandle mh(THREAD, Universe::finalizer_register_method());
+  JavaCalls::call(&result, mh, &args, CHECK_NULL);
+  mmtk_count_registered_finalizer();
+  return h_i();
+#else
+  add_finalizer((void*) obj);
+  return obj;
+#endif
+}
+
 void register_finalizer(void* obj) {
+#ifdef MMTK_ENABLE_FINAL_REFERENCES
+  // The caller keeps using its oop after this returns, so calling Finalizer.register() here could
+  // leave it with a stale oop if the call reaches a safepoint.  Instead, the binding keeps the
+  // object as a root, and the MMTk finalizer thread registers it soon after.
+  if (mmtk_defer_finalizer_registration(obj)) {
+    MMTkFinalizerThread::notify_finalizers_to_register();
+  }
+#else
   add_finalizer(obj);
+#endif
 }
 
 };
@@ -3,6 +3,8 @@
 
 #include "mmtkHeap.hpp"
 #include "mmtkMutator.hpp"
+#include "oops/instanceOop.hpp"
+#include "utilities/exceptions.hpp"
 
 typedef MMTkHeap ThirdPartyHeap;
 
@@ -11,5 +13,10 @@ namespace third_party_heap {
 
 typedef MMTkMutatorContext MutatorContext;
 
+// Register a finalizable object.  Return the object, which may have moved if the call reached a
+// safepoint.  InstanceKlass::register_finalizer() should call this as
+// `i = third_party_heap::register_finalizer(i, CHECK_NULL);`.
+instanceOop register_finalizer(instanceOop obj, TRAPS);
+
 }
 #endif // MMTK_OPENJDK_THIRD_PARTY_HEAP_HPP
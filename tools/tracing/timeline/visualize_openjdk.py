@@ -1,5 +1,27 @@
 #!/usr/bin/env python3
 
+REFERENCE_KINDS = ["soft", "weak", "final", "phantom"]
+
+# Must match `RootCategory` in `mmtk/src/root_stats.rs`.
+ROOT_CATEGORIES = [
+    "thread_stacks",
+    "conservative_stacks",
+    "universe",
+    "jni_handles",
+    "object_synchronizer",
+    "management",
+    "jvmti_export",
+    "aot_loader",
+    "system_dictionary",
+    "code_cache_nursery",
+    "code_cache_mature",
+    "string_table",
+    "class_loader_data_graph",
+    "weak_processor",
+    "vm_thread",
+    "finalization",
+]
+
 def enrich_meta_extra(log_processor, name, tid, ts, gc, wp, args):
     if wp is not None:
         match name:
@@ -11,3 +33,57 @@ def enrich_meta_extra(log_processor, name, tid, ts, gc, wp, args):
                     "mature_slots": mature,
                     "total_slots": total,
                 }
+
+            case "gc_cause":
+                wp["args"] |= {
+                    "cause": args[0],
+                }
+
+            case "reference_stats":
+                kind = REFERENCE_KINDS[int(args[0])]
+                discovered, cleared, retained, enqueued = [int(x) for x in args[1:5]]
+                wp["args"] |= {
+                    f"{kind}_refs": {
+                        "discovered": discovered,
+                        "cleared": cleared,
+                        "retained": retained,
+                        "enqueued": enqueued,
+                    },
+                }
+
+            case "stop_mutators":
+                wp["args"] |= {
+                    "mutators": int(args[0]),
+                }
+
+            case "scan_vm_roots":
+                wp["args"] |= {
+                    "root_category": ROOT_CATEGORIES[int(args[0])],
+                    "root_slots": int(args[1]),
+                }
+
+            case "scan_mutator_roots":
+                wp["args"] |= {
+                    "mutator": hex(int(args[0])),
+                    "stack_root_slots": int(args[1]),
+                }
+
+            case "enqueue_references":
+                enqueued = wp["args"].get("enqueued_references", 0)
+                wp["args"] |= {
+                    "enqueued_references": enqueued + int(args[0]),
+                }
+
+            case "schedule_finalizer":
+                registered, pending, finalized = [int(x) for x in args[0:3]]
+                wp["args"] |= {
+                    "finalizable_objects": {
+                        "registered": registered,
+                        "pending": pending,
+                        "finalized": finalized,
+                    },
+                }
+
+            # `register_nmethod`, `unregister_nmethod`, `bind_mutator` and `destroy_mutator`
+            # happen on mutator threads outside work packets, so they have no work packet to
+            # annotate.  They are still in the raw log for custom analysis.